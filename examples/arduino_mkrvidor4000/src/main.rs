@@ -37,17 +37,9 @@ fn main() -> ! {
         pins.scl.into_pad(&mut pins.port),
     );
 
-    // let mut charger_state = bq24195::ChargerState::try_new(&mut i2c).unwrap();
-    // // Configure for Charge Battery + Minimum System Voltage Limit: 3.5V
-    // charger_state
-    //     .write_power_on_configuration(
-    //         &mut i2c,
-    //         bq24195::PowerOnConfiguration::RESERVED
-    //             | bq24195::PowerOnConfiguration::SYS_MIN_0
-    //             | bq24195::PowerOnConfiguration::SYS_MIN_2
-    //             | bq24195::PowerOnConfiguration::CHG_CONFIG_0,
-    //     )
-    //     .unwrap();
+    // let mut charger_state = bq24195_i2c::ChargerState::try_new(&mut i2c).unwrap();
+    // // Configure for Charge Battery, keeping the Minimum System Voltage Limit
+    // charger_state.enable_charging(&mut i2c).unwrap();
 
     loop {}
 }
@@ -0,0 +1,355 @@
+//! Procedural macros for [bq24195-i2c](https://docs.rs/bq24195-i2c). Use them through the `macros` feature of that crate rather than depending on this one directly.
+
+extern crate proc_macro;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use std::convert::TryFrom;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Ident, Lit, Token};
+
+/// Build a `ChargerConfig` from physical values, checked at compile time.
+///
+/// Settings are written as `field = value`, using the datasheet field names in lower case.
+/// Settings that are not given keep their power-on defaults.
+///
+/// ```
+/// use bq24195_i2c::{bq24195_config, config::ChargerConfig};
+///
+/// static CONFIG: ChargerConfig = bq24195_config! {
+///     vreg = 4.192V,
+///     ichg = 1024mA,
+///     iinlim = 1500mA,
+///     watchdog = off,
+/// };
+///
+/// assert_eq!(CONFIG.registers()[0x04] >> 2, 43);
+/// ```
+///
+/// | Field         | Values                                              |
+/// |---------------|-----------------------------------------------------|
+/// | `en_hiz`      | `on`, `off`                                         |
+/// | `vindpm`      | 3.88V - 5.08V in 80mV steps                         |
+/// | `iinlim`      | 100mA, 150mA, 500mA, 900mA, 1.2A, 1.5A, 2A, 3A      |
+/// | `chg_config`  | `off`, `charge`, `otg`                              |
+/// | `sys_min`     | 3.0V - 3.7V in 100mV steps                          |
+/// | `ichg`        | 512mA - 4544mA in 64mA steps                        |
+/// | `force_20pct` | `on`, `off`                                         |
+/// | `iprechg`     | 128mA - 2048mA in 128mA steps                       |
+/// | `iterm`       | 128mA - 2048mA in 128mA steps                       |
+/// | `vreg`        | 3.504V - 4.4V in 16mV steps                         |
+/// | `batlowv`     | 2.8V, 3V                                            |
+/// | `vrechg`      | 100mV, 300mV                                        |
+/// | `en_term`     | `on`, `off`                                         |
+/// | `watchdog`    | `off`, 40s, 80s, 160s                               |
+/// | `chg_timer`   | `off`, 5h, 8h, 12h, 20h                             |
+/// | `tmr2x_en`    | `on`, `off`                                         |
+/// | `treg`        | 60C, 80C, 100C, 120C                                |
+///
+/// Voltages can be given in `V` or `mV` and currents in `A` or `mA`.
+///
+/// # Errors
+///
+/// Compilation fails if a value is out of range or between two settings:
+///
+/// ```compile_fail
+/// # use bq24195_i2c::{bq24195_config, config::ChargerConfig};
+/// // VREG can be 4.192V or 4.208V
+/// static CONFIG: ChargerConfig = bq24195_config! { vreg = 4.2V };
+/// ```
+///
+/// It also fails if settings contradict each other, including the defaults of settings that were not given:
+///
+/// ```compile_fail
+/// # use bq24195_i2c::{bq24195_config, config::ChargerConfig};
+/// // Charging will never terminate
+/// static CONFIG: ChargerConfig = bq24195_config! { ichg = 512mA, force_20pct = on, iterm = 128mA };
+/// ```
+///
+/// Unknown fields, missing units and repeated fields are errors as well.
+#[proc_macro]
+pub fn bq24195_config(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let settings =
+        parse_macro_input!(input with Punctuated::<Setting, Token![,]>::parse_terminated);
+    match expand(settings) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// A single `field = value`
+struct Setting {
+    field: Ident,
+    value: Value,
+}
+
+/// A number with a unit suffix, or a word like `off`
+enum Value {
+    Literal(Lit),
+    Word(Ident),
+}
+
+impl Parse for Setting {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let field = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = if input.peek(Lit) {
+            Value::Literal(input.parse()?)
+        } else {
+            Value::Word(input.parse()?)
+        };
+        Ok(Self { field, value })
+    }
+}
+
+impl Value {
+    fn span(&self) -> Span {
+        match self {
+            Value::Literal(lit) => lit.span(),
+            Value::Word(word) => word.span(),
+        }
+    }
+
+    /// A word from `words`
+    fn word(&self, words: &[&str], expected: &str) -> syn::Result<String> {
+        let word = match self {
+            Value::Word(word) => word.to_string(),
+            Value::Literal(Lit::Bool(lit)) => lit.value.to_string(),
+            Value::Literal(_) => String::new(),
+        };
+        if words.contains(&word.as_str()) {
+            Ok(word)
+        } else {
+            Err(syn::Error::new(
+                self.span(),
+                format!("expected {}", expected),
+            ))
+        }
+    }
+
+    /// `on` or `off`
+    fn switch(&self) -> syn::Result<bool> {
+        Ok(matches!(
+            self.word(&["on", "off", "true", "false"], "`on` or `off`")?
+                .as_str(),
+            "on" | "true"
+        ))
+    }
+
+    /// A number converted to the smallest unit in `units`. Each unit is a suffix and the number of smallest units it is worth.
+    fn quantity(&self, units: &[(&str, u32)]) -> syn::Result<u32> {
+        let error = |message: &str| syn::Error::new(self.span(), message);
+        let expected = || {
+            let units: Vec<_> = units.iter().map(|(unit, _)| *unit).collect();
+            error(&format!("expected a value in {}", units.join(" or ")))
+        };
+        let (digits, suffix) = match self {
+            Value::Literal(Lit::Int(lit)) => (lit.base10_digits(), lit.suffix()),
+            Value::Literal(Lit::Float(lit)) => (lit.base10_digits(), lit.suffix()),
+            _ => return Err(expected()),
+        };
+        let scale = match units.iter().find(|(unit, _)| *unit == suffix) {
+            Some((_, scale)) => *scale,
+            None => return Err(expected()),
+        };
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction.trim_end_matches('0')),
+            None => (digits, ""),
+        };
+        let mut value = whole
+            .parse::<u64>()
+            .map_err(|_| error("value is too large"))?
+            * u64::from(scale);
+        let mut place = u64::from(scale);
+        for digit in fraction.bytes() {
+            if place % 10 != 0 {
+                return Err(error("value is more precise than the field's unit"));
+            }
+            place /= 10;
+            value += u64::from(digit - b'0') * place;
+        }
+        u32::try_from(value).map_err(|_| error("value is too large"))
+    }
+}
+
+/// A value for a field with evenly spaced settings.
+///
+/// The range and step are checked by the `ChargerConfig` builder method during const evaluation, so they are only defined once, in the main crate.
+fn stepped(value: &Value, units: &[(&str, u32)]) -> syn::Result<u16> {
+    u16::try_from(value.quantity(units)?)
+        .map_err(|_| syn::Error::new(value.span(), "value is too large"))
+}
+
+/// A value from `options`
+fn one_of(
+    value: &Value,
+    name: &str,
+    unit: &str,
+    units: &[(&str, u32)],
+    options: &[u32],
+) -> syn::Result<u32> {
+    let quantity = value.quantity(units)?;
+    if options.contains(&quantity) {
+        Ok(quantity)
+    } else {
+        let options: Vec<_> = options
+            .iter()
+            .map(|option| format!("{}{}", option, unit))
+            .collect();
+        Err(syn::Error::new(
+            value.span(),
+            format!("{} must be one of {}", name, options.join(", ")),
+        ))
+    }
+}
+
+const MILLIVOLTS: &[(&str, u32)] = &[("V", 1000), ("mV", 1)];
+const MILLIAMPS: &[(&str, u32)] = &[("A", 1000), ("mA", 1)];
+
+/// Builder calls for each setting, ending with `assert_valid` so that ranges, steps and contradictory settings are checked
+/// during const evaluation by the main crate, where they are defined.
+fn expand(settings: Punctuated<Setting, Token![,]>) -> syn::Result<TokenStream> {
+    let mut seen: Vec<String> = Vec::new();
+    let mut calls = Vec::new();
+
+    for Setting { field, value } in settings.iter() {
+        let name = field.to_string();
+        if seen.contains(&name) {
+            return Err(syn::Error::new(
+                field.span(),
+                format!("`{}` is set more than once", name),
+            ));
+        }
+        seen.push(name.clone());
+
+        let control = quote!(::bq24195_i2c::control);
+        let call = match name.as_str() {
+            "en_hiz" => {
+                let hiz = value.switch()?;
+                quote!(hiz(#hiz))
+            }
+            "vindpm" => {
+                let mv = stepped(value, MILLIVOLTS)?;
+                quote!(vindpm_millivolts(#mv))
+            }
+            "iinlim" => {
+                let ma = one_of(
+                    value,
+                    "IINLIM",
+                    "mA",
+                    MILLIAMPS,
+                    &[100, 150, 500, 900, 1200, 1500, 2000, 3000],
+                )?;
+                let variant = Ident::new(&format!("Milliamps{}", ma), Span::call_site());
+                quote!(input_current_limit(#control::InputCurrentLimit::#variant))
+            }
+            "chg_config" => {
+                let mode = match value
+                    .word(&["off", "charge", "otg"], "`off`, `charge` or `otg`")?
+                    .as_str()
+                {
+                    "off" => quote!(ChargeDisabled),
+                    "charge" => quote!(Charge),
+                    _ => quote!(Otg),
+                };
+                quote!(charger_mode(#control::ChargerMode::#mode))
+            }
+            "sys_min" => {
+                let mv = stepped(value, MILLIVOLTS)?;
+                quote!(system_minimum_millivolts(#mv))
+            }
+            "ichg" => {
+                let ma = stepped(value, MILLIAMPS)?;
+                quote!(charge_current_milliamps(#ma))
+            }
+            "force_20pct" => {
+                let force = value.switch()?;
+                quote!(force_20pct(#force))
+            }
+            "iprechg" => {
+                let ma = stepped(value, MILLIAMPS)?;
+                quote!(precharge_current_milliamps(#ma))
+            }
+            "iterm" => {
+                let ma = stepped(value, MILLIAMPS)?;
+                quote!(termination_current_milliamps(#ma))
+            }
+            "vreg" => {
+                let mv = stepped(value, MILLIVOLTS)?;
+                quote!(charge_voltage_millivolts(#mv))
+            }
+            "batlowv" => {
+                let variant = match one_of(value, "BATLOWV", "mV", MILLIVOLTS, &[2800, 3000])? {
+                    2800 => quote!(Millivolts2800),
+                    _ => quote!(Millivolts3000),
+                };
+                quote!(battery_low_voltage(#control::BatteryLowVoltage::#variant))
+            }
+            "vrechg" => {
+                let variant = match one_of(value, "VRECHG", "mV", MILLIVOLTS, &[100, 300])? {
+                    100 => quote!(Millivolts100),
+                    _ => quote!(Millivolts300),
+                };
+                quote!(recharge_threshold(#control::RechargeThreshold::#variant))
+            }
+            "en_term" => {
+                let enabled = value.switch()?;
+                quote!(termination(#enabled))
+            }
+            "watchdog" => {
+                let variant = if value.word(&["off"], "").is_ok() {
+                    quote!(Disabled)
+                } else {
+                    let seconds = one_of(value, "WATCHDOG", "s", &[("s", 1)], &[40, 80, 160])
+                        .map_err(|error| {
+                            syn::Error::new(error.span(), format!("{}, or `off`", error))
+                        })?;
+                    let variant = Ident::new(&format!("Seconds{}", seconds), Span::call_site());
+                    quote!(#variant)
+                };
+                quote!(watchdog(#control::Watchdog::#variant))
+            }
+            "chg_timer" => {
+                if value.word(&["off"], "").is_ok() {
+                    quote!(safety_timer(::core::option::Option::None))
+                } else {
+                    let hours = one_of(value, "CHG_TIMER", "h", &[("h", 1)], &[5, 8, 12, 20])
+                        .map_err(|error| {
+                            syn::Error::new(error.span(), format!("{}, or `off`", error))
+                        })?;
+                    let variant = Ident::new(&format!("Hours{}", hours), Span::call_site());
+                    quote!(safety_timer(::core::option::Option::Some(
+                        #control::SafetyTimer::#variant
+                    )))
+                }
+            }
+            "tmr2x_en" => {
+                let enabled = value.switch()?;
+                quote!(safety_timer_2x(#enabled))
+            }
+            "treg" => {
+                let celsius = one_of(value, "TREG", "C", &[("C", 1)], &[60, 80, 100, 120])?;
+                let variant = Ident::new(&format!("Celsius{}", celsius), Span::call_site());
+                quote!(thermal_regulation_threshold(
+                    #control::ThermalRegulationThreshold::#variant
+                ))
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    field.span(),
+                    format!("unknown field `{}`", name),
+                ))
+            }
+        };
+        calls.push(call);
+    }
+
+    Ok(quote! {
+        {
+            const CONFIG: ::bq24195_i2c::config::ChargerConfig =
+                ::bq24195_i2c::config::ChargerConfig::new() #(.#calls)*.assert_valid();
+            CONFIG
+        }
+    })
+}
@@ -0,0 +1,276 @@
+//! Adaptive input current limit search for unknown input sources.
+//!
+//! USB detection picks a conservative [`InputSourceControl::IINLIM[2:0]`](../struct.InputSourceControl.html#associatedconstant.IINLIM_2) for anything that is not a recognized charging port.
+//! [`ChargerState::adapt_input_current_limit`](../struct.ChargerState.html#method.adapt_input_current_limit) steps the limit up one setting at a time while
+//! [`SystemStatus::DPM_STAT`](../struct.SystemStatus.html#associatedconstant.DPM_STAT) shows the input is the bottleneck, and backs off to the highest setting that did not collapse the source.
+//! A setting collapses the source if [`SystemStatus::PG_STAT`](../struct.SystemStatus.html#associatedconstant.PG_STAT) goes low, an input fault is reported,
+//! or DPM is still set once the step has settled for twice the settle time.
+//!
+//! DPM_STAT does not distinguish input current regulation from input voltage regulation, so sustained DPM after a step is taken to mean the source is sagging to VINDPM.
+//! A load that needs more than the next setting also keeps DPM set, so the search is conservative and may stop below what the source could supply.
+
+use crate::control::InputCurrentLimit;
+use crate::status::ChargeFault;
+use crate::{ChargerState, Error};
+use hal::blocking::delay::DelayMs;
+use hal::blocking::i2c::{Write, WriteRead};
+
+/// Input current ceiling in mA set by the resistor on the ILIM pin: `ILIM = 1V / R_ILIM * 530`
+///
+/// A 0 ohm resistor is treated as no ceiling.
+pub const fn ilim_milliamps(r_ilim_ohms: u32) -> u32 {
+    match 530_000u32.checked_div(r_ilim_ohms) {
+        Some(ilim) => ilim,
+        None => u32::MAX,
+    }
+}
+
+/// Options for [`ChargerState::adapt_input_current_limit`](../struct.ChargerState.html#method.adapt_input_current_limit)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputCurrentSearch {
+    /// Highest limit to try
+    pub max: InputCurrentLimit,
+    /// Resistance of the ILIM pin resistor in ohms, if known. Settings above the ILIM ceiling are not tried since they have no effect.
+    pub r_ilim_ohms: Option<u32>,
+    /// Time to wait after each step before checking status, in ms
+    pub settle_ms: u16,
+}
+
+impl InputCurrentSearch {
+    /// Highest setting allowed by both [`max`](#structfield.max) and the ILIM pin
+    pub fn ceiling(&self) -> InputCurrentLimit {
+        let ilim = self.r_ilim_ohms.map(ilim_milliamps);
+        InputCurrentLimit::ALL
+            .iter()
+            .copied()
+            .rev()
+            .find(|limit| {
+                *limit <= self.max
+                    && !matches!(ilim, Some(ilim) if u32::from(limit.milliamps()) > ilim)
+            })
+            .unwrap_or(InputCurrentLimit::Milliamps100)
+    }
+}
+
+impl ChargerState {
+    /// Step [`InputSourceControl::IINLIM[2:0]`](struct.InputSourceControl.html#associatedconstant.IINLIM_2) up from its current setting to the highest one the source can supply,
+    /// and return the setting that was left in place.
+    ///
+    /// The search stops when DPM is no longer active, when the ceiling is reached, or when a step collapses the source or leaves DPM set,
+    /// in which case the previous setting is restored.
+    /// If the current setting is above the ceiling, it is lowered to the ceiling first.
+    ///
+    /// [`Error::NoInputPower`](enum.Error.html#variant.NoInputPower) is returned if there is no good input source to begin with.
+    pub fn adapt_input_current_limit<E, I2C, D>(
+        &mut self,
+        i2c: &mut I2C,
+        delay: &mut D,
+        search: InputCurrentSearch,
+    ) -> Result<InputCurrentLimit, Error<E>>
+    where
+        I2C: Write<Error = E> + WriteRead<Error = E>,
+        D: DelayMs<u16>,
+    {
+        // Clear any fault latched before the search started
+        self.read_fault(i2c).map_err(Error::I2c)?;
+        self.read_system_status(i2c).map_err(Error::I2c)?;
+        if !self.get_system_status().power_good() {
+            return Err(Error::NoInputPower);
+        }
+
+        let ceiling = search.ceiling();
+        self.read_input_source_control(i2c).map_err(Error::I2c)?;
+        let mut current = self.get_input_source_control().input_current_limit();
+        if current > ceiling {
+            self.write_input_current_limit(i2c, ceiling)
+                .map_err(Error::I2c)?;
+            current = ceiling;
+        }
+
+        while self.get_system_status().dpm() && current < ceiling {
+            let next = InputCurrentLimit::from_bits(current.bits() + 1);
+            self.write_input_current_limit(i2c, next)
+                .map_err(Error::I2c)?;
+            delay.delay_ms(search.settle_ms);
+            self.read_status(i2c).map_err(Error::I2c)?;
+            let mut collapsed = self.source_collapsed();
+            if !collapsed && self.get_system_status().dpm() {
+                // DPM right after a step may only be the load catching up, so check it is sustained
+                delay.delay_ms(search.settle_ms);
+                self.read_status(i2c).map_err(Error::I2c)?;
+                collapsed = self.source_collapsed() || self.get_system_status().dpm();
+            }
+            if collapsed {
+                self.write_input_current_limit(i2c, current)
+                    .map_err(Error::I2c)?;
+                break;
+            }
+            current = next;
+        }
+        Ok(current)
+    }
+
+    /// True if the last status read shows the input source is no longer good or has faulted
+    fn source_collapsed(&self) -> bool {
+        !self.get_system_status().power_good()
+            || self.get_fault().charge_fault() == ChargeFault::InputFault
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{ScriptedBus, SimulatedCharger};
+    use crate::NUM_REGISTERS;
+
+    const POWER_GOOD: u8 = 0b0000_0100;
+    const DPM: u8 = 0b0000_1000;
+    const INPUT_FAULT: u8 = 0b0001_0000;
+
+    struct NoDelay;
+
+    impl DelayMs<u16> for NoDelay {
+        fn delay_ms(&mut self, _: u16) {}
+    }
+
+    /// Bus for a source that collapses above `capacity_ma`, either by losing power good or with an input fault,
+    /// and a load that keeps DPM set below `demand_ma`
+    fn source(
+        initial: InputCurrentLimit,
+        capacity_ma: u16,
+        demand_ma: u16,
+        input_fault: bool,
+    ) -> ScriptedBus<'static> {
+        let respond = move |sim: &mut SimulatedCharger| {
+            let limit = InputCurrentLimit::from_bits(sim.registers()[0x00]).milliamps();
+            let (status, fault) = if limit > capacity_ma && input_fault {
+                (POWER_GOOD, INPUT_FAULT)
+            } else if limit > capacity_ma {
+                (0, 0)
+            } else if limit < demand_ma {
+                (POWER_GOOD | DPM, 0)
+            } else {
+                (POWER_GOOD, 0)
+            };
+            sim.set_register(0x08, status);
+            sim.set_register(0x09, fault);
+        };
+        let mut sim = SimulatedCharger::new();
+        sim.set_register(0x00, 0x30 | initial.bits());
+        respond(&mut sim);
+        ScriptedBus::with_script(sim, respond)
+    }
+
+    fn search(max: InputCurrentLimit, r_ilim_ohms: Option<u32>) -> InputCurrentSearch {
+        InputCurrentSearch {
+            max,
+            r_ilim_ohms,
+            settle_ms: 100,
+        }
+    }
+
+    fn adapt(
+        i2c: &mut ScriptedBus,
+        search: InputCurrentSearch,
+    ) -> Result<InputCurrentLimit, Error<crate::sim::SimError>> {
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        state.adapt_input_current_limit(i2c, &mut NoDelay, search)
+    }
+
+    #[test]
+    fn steps_up_while_dpm_is_set() {
+        let mut i2c = source(InputCurrentLimit::Milliamps500, 3000, 800, false);
+        assert_eq!(
+            adapt(&mut i2c, search(InputCurrentLimit::Milliamps3000, None)),
+            Ok(InputCurrentLimit::Milliamps900)
+        );
+        assert_eq!(i2c.writes, [[0x00, 0x33]]);
+    }
+
+    #[test]
+    fn stays_put_without_dpm() {
+        let mut i2c = source(InputCurrentLimit::Milliamps500, 3000, 400, false);
+        assert_eq!(
+            adapt(&mut i2c, search(InputCurrentLimit::Milliamps3000, None)),
+            Ok(InputCurrentLimit::Milliamps500)
+        );
+        assert!(i2c.writes.is_empty());
+    }
+
+    #[test]
+    fn backs_off_when_power_good_drops() {
+        let mut i2c = source(InputCurrentLimit::Milliamps500, 700, 3000, false);
+        assert_eq!(
+            adapt(&mut i2c, search(InputCurrentLimit::Milliamps3000, None)),
+            Ok(InputCurrentLimit::Milliamps500)
+        );
+        assert_eq!(i2c.writes, [[0x00, 0x33], [0x00, 0x32]]);
+    }
+
+    #[test]
+    fn backs_off_on_input_fault() {
+        let mut i2c = source(InputCurrentLimit::Milliamps500, 700, 3000, true);
+        assert_eq!(
+            adapt(&mut i2c, search(InputCurrentLimit::Milliamps3000, None)),
+            Ok(InputCurrentLimit::Milliamps500)
+        );
+        assert_eq!(i2c.writes, [[0x00, 0x33], [0x00, 0x32]]);
+    }
+
+    #[test]
+    fn backs_off_on_sustained_dpm() {
+        let mut i2c = source(InputCurrentLimit::Milliamps500, 3000, 1400, false);
+        assert_eq!(
+            adapt(&mut i2c, search(InputCurrentLimit::Milliamps3000, None)),
+            Ok(InputCurrentLimit::Milliamps500)
+        );
+        assert_eq!(i2c.writes, [[0x00, 0x33], [0x00, 0x32]]);
+    }
+
+    #[test]
+    fn stops_at_ceiling() {
+        let mut i2c = source(InputCurrentLimit::Milliamps1200, 3000, 3000, false);
+        assert_eq!(
+            adapt(&mut i2c, search(InputCurrentLimit::Milliamps1200, None)),
+            Ok(InputCurrentLimit::Milliamps1200)
+        );
+        assert!(i2c.writes.is_empty());
+    }
+
+    #[test]
+    fn lowers_setting_above_ceiling() {
+        let mut i2c = source(InputCurrentLimit::Milliamps2000, 3000, 3000, false);
+        assert_eq!(
+            adapt(&mut i2c, search(InputCurrentLimit::Milliamps1200, None)),
+            Ok(InputCurrentLimit::Milliamps1200)
+        );
+        assert_eq!(i2c.writes, [[0x00, 0x34]]);
+    }
+
+    #[test]
+    fn stops_at_ilim_resistor_limit() {
+        // 530 ohm sets a 1A ceiling
+        let mut i2c = source(InputCurrentLimit::Milliamps900, 3000, 3000, false);
+        assert_eq!(
+            adapt(
+                &mut i2c,
+                search(InputCurrentLimit::Milliamps3000, Some(530))
+            ),
+            Ok(InputCurrentLimit::Milliamps900)
+        );
+        assert!(i2c.writes.is_empty());
+    }
+
+    #[test]
+    fn needs_input_power() {
+        let mut i2c = source(InputCurrentLimit::Milliamps500, 3000, 3000, false);
+        i2c.sim.set_register(0x08, 0);
+        assert_eq!(
+            adapt(&mut i2c, search(InputCurrentLimit::Milliamps3000, None)),
+            Err(Error::NoInputPower)
+        );
+        assert!(i2c.writes.is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+//! Inspect and configure a BQ24195 from Linux userspace over i2c-dev.
+//!
+//! ```text
+//! bq24195ctl [--bus /dev/i2c-1 | --sim | --sim-detecting] <command>
+//!
+//! dump                          decode every register
+//! get <field>                   read one field, i.e. `get VREG`
+//! set <field> <value>           write one field, i.e. `set IINLIM 1.5A`
+//! watch [--interval ms] [--count n]
+//!                               print status and fault events as they happen
+//! apply <config-file>           write `FIELD = value` lines, one per line, then validate
+//! calc [--c prefix] [bytes...] [FIELD=value...]
+//!                               calculate registers 0x00 - 0x07 without a charger, i.e. `calc VREG=4.2V ICHG=1.5A`
+//! ```
+//!
+//! `--sim` runs against a [`SimulatedCharger`](../bq24195_i2c/sim/struct.SimulatedCharger.html) instead of a real bus.
+//! `--sim-detecting` does the same, but the simulated charger is in the middle of D+/D- detection, so DPDM_EN is set and stays as written.
+//!
+//! `set` and `apply` write DPDM_EN as 0 unless it is given, so that they do not force another detection.
+
+use bq24195_i2c::calc::{CalcError, Calculation};
+use bq24195_i2c::config::ChargerConfig;
+use bq24195_i2c::event::EventMonitor;
+use bq24195_i2c::field::{Encoding, Field, FIELDS};
+use bq24195_i2c::sim::SimulatedCharger;
+use bq24195_i2c::{ChargerState, MiscOperationControl, LAST_WRITABLE_REGISTER};
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use linux_embedded_hal::I2cdev;
+use std::fmt::Debug;
+use std::time::Duration;
+use std::{env, fs, process, thread};
+
+const USAGE: &str = "usage: bq24195ctl [--bus /dev/i2c-1 | --sim | --sim-detecting] <command>
+
+commands:
+    dump                                decode every register
+    get <field>                         read one field, i.e. `get VREG`
+    set <field> <value>                 write one field, i.e. `set IINLIM 1.5A`
+    watch [--interval ms] [--count n]   print status and fault events as they happen
+    apply <config-file>                 write `FIELD = value` lines, then validate
+    calc [--c prefix] [bytes...] [FIELD=value...]
+                                        calculate registers 0x00 - 0x07 without a charger, starting from
+                                        the given bytes or the defaults; --c prints C #defines";
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut bus = String::from("/dev/i2c-1");
+    let mut sim = false;
+    let mut detecting = false;
+    while let Some(option) = args.first().filter(|arg| arg.starts_with("--")) {
+        match option.as_str() {
+            "--sim" => {
+                sim = true;
+                args.remove(0);
+            }
+            "--sim-detecting" => {
+                sim = true;
+                detecting = true;
+                args.remove(0);
+            }
+            "--bus" if args.len() >= 2 => {
+                bus = args.remove(1);
+                args.remove(0);
+            }
+            "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => fail(USAGE),
+        }
+    }
+
+    let result = if args.first().map(String::as_str) == Some("calc") {
+        calc(&args[1..])
+    } else if sim {
+        let mut charger = SimulatedCharger::new();
+        if detecting {
+            let misc_operation_control = charger.registers()[0x07];
+            charger.set_register(
+                0x07,
+                misc_operation_control | MiscOperationControl::DPDM_EN.bits(),
+            );
+            charger.hold_detection(true);
+        }
+        run(&mut charger, &args)
+    } else {
+        match I2cdev::new(&bus) {
+            Ok(mut i2c) => run(&mut i2c, &args),
+            Err(err) => Err(format!("cannot open {}: {}", bus, err)),
+        }
+    };
+    if let Err(message) = result {
+        fail(&message);
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn run<E: Debug, I2C: Write<Error = E> + WriteRead<Error = E>>(
+    i2c: &mut I2C,
+    args: &[String],
+) -> Result<(), String> {
+    let mut state = ChargerState::try_new(i2c).map_err(i2c_error)?;
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["dump"] => {
+            print!("{}", state);
+            println!("{}", state.status_summary());
+        }
+        ["get", name] => {
+            let field = field(name)?;
+            let bits = state.registers()[usize::from(field.register)];
+            println!("{}={}", field.name, field.decode(bits));
+        }
+        ["set", name, value] => {
+            let field = field(name)?;
+            let mut registers = registers_to_write(&state);
+            set(&mut registers, field, value)?;
+            write(i2c, &mut state, registers)?;
+            let bits = state.registers()[usize::from(field.register)];
+            println!("{}={}", field.name, field.decode(bits));
+        }
+        ["watch", options @ ..] => watch(i2c, &mut state, options)?,
+        ["apply", path] => {
+            let config =
+                fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+            let mut registers = registers_to_write(&state);
+            for (index, line) in config.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or_default().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let (name, value) = match line.find('=') {
+                    Some(equals) => (&line[..equals], &line[equals + 1..]),
+                    None => {
+                        return Err(format!("{}:{}: expected `FIELD = value`", path, index + 1))
+                    }
+                };
+                set(&mut registers, field(name.trim())?, value.trim())
+                    .map_err(|err| format!("{}:{}: {}", path, index + 1, err))?;
+            }
+            let before = state.clone();
+            write(i2c, &mut state, registers)?;
+            for change in before.diff(&state) {
+                println!("{}: {} -> {}", change.field.name, change.old, change.new);
+            }
+        }
+        _ => return Err(USAGE.into()),
+    }
+    Ok(())
+}
+
+fn i2c_error<E: Debug>(err: E) -> String {
+    format!("I2C error: {:?}", err)
+}
+
+fn field(name: &str) -> Result<&'static Field, String> {
+    Field::by_name(name).ok_or_else(|| {
+        let names: Vec<_> = FIELDS.iter().map(|field| field.name).collect();
+        format!(
+            "unknown field {}, expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+/// Registers of the chip state, to be changed and written back.
+///
+/// DPDM_EN reads as 1 while D+/D- detection is running, so it is cleared to keep the write from forcing another detection.
+/// Setting the DPDM_EN field explicitly still forces one.
+fn registers_to_write(state: &ChargerState) -> [u8; 11] {
+    let mut registers = state.registers();
+    registers[0x07] &= !MiscOperationControl::DPDM_EN.bits();
+    registers
+}
+
+/// Encode `text` into `field` of the register values
+fn set(registers: &mut [u8], field: &Field, text: &str) -> Result<(), String> {
+    if field.register > LAST_WRITABLE_REGISTER {
+        return Err(format!("{} is read-only", field.name));
+    }
+    let raw = field
+        .parse(text)
+        .and_then(|value| field.encode(value))
+        .ok_or_else(|| {
+            format!(
+                "{} is not a setting of {}, expected {}",
+                text,
+                field.name,
+                settings(field)
+            )
+        })?;
+    let register = &mut registers[usize::from(field.register)];
+    *register = (*register & !field.mask()) | (raw << field.shift);
+    Ok(())
+}
+
+/// Description of the settings of a field
+fn settings(field: &Field) -> String {
+    match field.encoding {
+        Encoding::Flag => "0 or 1".into(),
+        Encoding::Linear { offset, step, unit } => {
+            let max = offset + step * ((1 << field.width) - 1);
+            format!(
+                "{}{} to {}{} in {}{} steps",
+                offset, unit, max, unit, step, unit
+            )
+        }
+        Encoding::Table { values, unit } => {
+            let values: Vec<_> = values
+                .iter()
+                .map(|value| format!("{}{}", value, unit))
+                .collect();
+            format!("one of {}", values.join(", "))
+        }
+        Encoding::Named(names) => format!("one of {}", names.join(", ")),
+        Encoding::Raw => format!("a {}-bit number", field.width),
+    }
+}
+
+/// Validate the writable registers and write them all
+fn write<E: Debug, I2C: Write<Error = E> + WriteRead<Error = E>>(
+    i2c: &mut I2C,
+    state: &mut ChargerState,
+    registers: [u8; 11],
+) -> Result<(), String> {
+    let mut next = ChargerState::from_registers(registers);
+    let violations = next.validate();
+    if !violations.is_empty() {
+        let violations: Vec<_> = violations
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect();
+        return Err(format!(
+            "not written, breaks datasheet constraints: {}",
+            violations.join(", ")
+        ));
+    }
+    next.write_all(i2c).map_err(i2c_error)?;
+    state.read_all(i2c).map_err(i2c_error)
+}
+
+fn calc(args: &[String]) -> Result<(), String> {
+    let (prefix, args) = match args {
+        [option, prefix, args @ ..] if option == "--c" => (Some(prefix.as_str()), args),
+        _ => (None, args),
+    };
+    // Leading hex bytes are the starting values of registers 0x00, 0x01, ...
+    let bytes = args.iter().take_while(|arg| !arg.contains('=')).count();
+    let mut registers = ChargerConfig::new().registers();
+    if bytes > registers.len() {
+        return Err(format!(
+            "expected at most {} register bytes",
+            registers.len()
+        ));
+    }
+    for (register, byte) in registers.iter_mut().zip(&args[..bytes]) {
+        let digits = byte.trim_start_matches("0x").trim_start_matches("0X");
+        *register =
+            u8::from_str_radix(digits, 16).map_err(|_| format!("{} is not a hex byte", byte))?;
+    }
+
+    let mut calculation = Calculation::from_config(ChargerConfig::from_registers(registers));
+    for request in &args[bytes..] {
+        let (name, text) = request
+            .split_once('=')
+            .ok_or_else(|| format!("{}: expected FIELD=value", request))?;
+        let field = field(name)?;
+        let value = field.parse(text).ok_or_else(|| {
+            format!(
+                "{} is not a value of {}, expected {}",
+                text,
+                field.name,
+                settings(field)
+            )
+        })?;
+        calculation.request(field, value).map_err(|err| match err {
+            CalcError::ReadOnly => format!("{} is read-only", field.name),
+            CalcError::NotASetting => format!(
+                "{} is not a setting of {}, expected {}",
+                text,
+                field.name,
+                settings(field)
+            ),
+        })?;
+    }
+
+    match prefix {
+        Some(prefix) => print!("{}", calculation.c_defines(prefix)),
+        None => print!("{}", calculation),
+    }
+    Ok(())
+}
+
+fn watch<E: Debug, I2C: Write<Error = E> + WriteRead<Error = E>>(
+    i2c: &mut I2C,
+    state: &mut ChargerState,
+    options: &[&str],
+) -> Result<(), String> {
+    let mut interval = Duration::from_secs(1);
+    let mut count = None;
+    for option in options.chunks(2) {
+        let value = option
+            .get(1)
+            .and_then(|value| value.parse::<u64>().ok())
+            .ok_or_else(|| String::from(USAGE))?;
+        match option[0] {
+            "--interval" => interval = Duration::from_millis(value),
+            "--count" => count = Some(value),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    println!("{}", state.status_summary());
+    let mut monitor = EventMonitor::new(state.get_system_status(), state.get_fault());
+    let mut polls = 0;
+    while !matches!(count, Some(count) if polls >= count) {
+        thread::sleep(interval);
+        state.read_status(i2c).map_err(i2c_error)?;
+        monitor.update(state.get_system_status(), state.get_fault());
+        while let Some(event) = monitor.next_event() {
+            println!("{:?}", event);
+        }
+        polls += 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,245 @@
+//! Register calculator for planning a configuration without a board.
+//!
+//! A [`Calculation`](struct.Calculation.html) starts from the power-on defaults (or any register image) and takes physical values for fields,
+//! rounding each quantity down to a setting the field has, so that no voltage or current limit ends up above the request. It then shows the bytes for the writable registers (0x00 - 0x07),
+//! the value each requested field actually gets, and any [`Violation`](../validate/enum.Violation.html)s of the result.
+//! [`c_defines`](struct.Calculation.html#method.c_defines) emits the bytes as C `#define`s for firmware written in other languages.
+//!
+//! ```
+//! use bq24195_i2c::calc::Calculation;
+//! use bq24195_i2c::field::{Field, Unit, Value};
+//!
+//! let mut calculation = Calculation::new();
+//! let vreg = Field::by_name("VREG").unwrap();
+//! let actual = calculation.request(vreg, Value::Quantity(4200, Unit::Millivolts)).unwrap();
+//! assert_eq!(actual, Value::Quantity(4192, Unit::Millivolts));
+//! assert_eq!(calculation.config().registers()[0x04], 0xAE);
+//! assert!(calculation.violations().is_empty());
+//!
+//! let defines = calculation.c_defines("BQ24195").to_string();
+//! assert!(defines.contains("#define BQ24195_REG04 0xAE"));
+//! ```
+
+use crate::config::ChargerConfig;
+use crate::field::{Encoding, Field, Value, FIELDS};
+use crate::validate::Violations;
+use crate::{LAST_WRITABLE_REGISTER, REGISTER_NAMES};
+use core::fmt;
+
+/// Reason a value could not be requested
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CalcError {
+    /// The field is in a status register (0x08 - 0x0A)
+    ReadOnly,
+    /// The value is not of the field's kind or unit, or is not one of its names
+    NotASetting,
+}
+
+/// A requested field value and the value it was rounded to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Setting {
+    /// The requested field
+    pub field: &'static Field,
+    /// Value as requested
+    pub requested: Value,
+    /// Value the registers hold
+    pub actual: Value,
+}
+
+/// Image of the writable registers with the values requested for each field
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Calculation {
+    config: ChargerConfig,
+    /// Requested value of each field, indexed like [`FIELDS`]
+    requested: [Option<Value>; FIELDS.len()],
+}
+
+impl Default for Calculation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calculation {
+    /// Calculation starting from the power-on defaults
+    pub fn new() -> Self {
+        Self::from_config(ChargerConfig::new())
+    }
+
+    /// Calculation starting from a register image, i.e. to decode it
+    pub fn from_config(config: ChargerConfig) -> Self {
+        Self {
+            config,
+            requested: [None; FIELDS.len()],
+        }
+    }
+
+    /// Set a field to the highest setting not above `value`, returning the value it actually gets.
+    ///
+    /// Quantities are rounded down to a step, and clamped to the field's range, so a value below the lowest setting gets that setting.
+    /// Flags, names and raw bits must be exact.
+    pub fn request(&mut self, field: &'static Field, value: Value) -> Result<Value, CalcError> {
+        if field.register > LAST_WRITABLE_REGISTER {
+            return Err(CalcError::ReadOnly);
+        }
+        let raw = quantize(field, value).ok_or(CalcError::NotASetting)?;
+        let mut registers = self.config.registers();
+        let register = usize::from(field.register);
+        registers[register] = (registers[register] & !field.mask()) | (raw << field.shift);
+        self.config = ChargerConfig::from_registers(registers);
+        if let Some(index) = FIELDS.iter().position(|f| f == field) {
+            self.requested[index] = Some(value);
+        }
+        Ok(field.decode(registers[register]))
+    }
+
+    /// The resulting configuration
+    pub fn config(&self) -> ChargerConfig {
+        self.config
+    }
+
+    /// Requested fields in [`FIELDS`](../field/constant.FIELDS.html) order, with the values they actually get
+    pub fn settings(&self) -> impl Iterator<Item = Setting> + '_ {
+        let registers = self.config.registers();
+        FIELDS
+            .iter()
+            .zip(self.requested.iter())
+            .filter_map(move |(field, requested)| {
+                requested.map(|requested| Setting {
+                    field,
+                    requested,
+                    actual: field.decode(registers[usize::from(field.register)]),
+                })
+            })
+    }
+
+    /// Datasheet constraints the configuration breaks, see [`ChargerConfig::validate`](../config/struct.ChargerConfig.html#method.validate)
+    pub fn violations(&self) -> Violations {
+        self.config.validate()
+    }
+
+    /// The registers as C `#define`s named `{prefix}_REG00` to `{prefix}_REG07`, each commented with its decoded fields
+    pub fn c_defines<'a>(&'a self, prefix: &'a str) -> CDefines<'a> {
+        CDefines {
+            config: &self.config,
+            prefix,
+        }
+    }
+}
+
+/// Shows each register with its decoded fields, then the requested values and violations
+impl fmt::Display for Calculation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (address, bits) in self.config.registers().iter().enumerate() {
+            write!(
+                f,
+                "{:#04x} = {:#04x} {}: ",
+                address, bits, REGISTER_NAMES[address]
+            )?;
+            crate::field::fmt_fields(f, address as u8, *bits)?;
+            writeln!(f)?;
+        }
+        for setting in self.settings() {
+            write!(f, "{}: {}", setting.field.name, setting.actual)?;
+            if setting.actual != setting.requested {
+                write!(f, " (requested {})", setting.requested)?;
+            }
+            writeln!(f)?;
+        }
+        for violation in self.violations().iter() {
+            writeln!(f, "warning: {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+/// C `#define`s for a [`Calculation`](struct.Calculation.html), see [`Calculation::c_defines`](struct.Calculation.html#method.c_defines)
+pub struct CDefines<'a> {
+    config: &'a ChargerConfig,
+    prefix: &'a str,
+}
+
+impl fmt::Display for CDefines<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (address, bits) in self.config.registers().iter().enumerate() {
+            write!(
+                f,
+                "#define {}_REG{:02X} {:#04X} /* {}: ",
+                self.prefix, address, bits, REGISTER_NAMES[address]
+            )?;
+            crate::field::fmt_fields(f, address as u8, *bits)?;
+            writeln!(f, " */")?;
+        }
+        Ok(())
+    }
+}
+
+/// Raw bits of the highest setting not above `value`, or of the lowest setting
+fn quantize(field: &Field, value: Value) -> Option<u8> {
+    let max = (1u16 << field.width) - 1;
+    match (field.encoding, value) {
+        (Encoding::Linear { offset, step, unit }, Value::Quantity(value, value_unit))
+            if unit == value_unit =>
+        {
+            let raw = value.saturating_sub(offset) / step;
+            Some(raw.min(max) as u8)
+        }
+        (Encoding::Table { values, unit }, Value::Quantity(value, value_unit))
+            if unit == value_unit =>
+        {
+            // The table is in ascending order
+            let raw = values
+                .iter()
+                .rposition(|setting| *setting <= value)
+                .unwrap_or(0);
+            Some(raw as u8)
+        }
+        _ => field.encode(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Unit;
+
+    fn quantized(name: &str, value: Value) -> Option<u8> {
+        quantize(Field::by_name(name).unwrap(), value)
+    }
+
+    #[test]
+    fn linear_rounds_down() {
+        let vreg = |millivolts| quantized("VREG", Value::Quantity(millivolts, Unit::Millivolts));
+        assert_eq!(vreg(4192), Some(43));
+        assert_eq!(vreg(4200), Some(43));
+        assert_eq!(vreg(4207), Some(43));
+        assert_eq!(vreg(4208), Some(44));
+        // 4.336V rather than 4.352V, which would overcharge a 4.35V cell
+        assert_eq!(vreg(4350), Some(52));
+        assert_eq!(vreg(3000), Some(0));
+        assert_eq!(vreg(u16::MAX), Some(63));
+    }
+
+    #[test]
+    fn table_rounds_down() {
+        let iinlim = |milliamps| quantized("IINLIM", Value::Quantity(milliamps, Unit::Milliamps));
+        assert_eq!(iinlim(1200), Some(4));
+        assert_eq!(iinlim(1499), Some(4));
+        assert_eq!(iinlim(1500), Some(5));
+        assert_eq!(iinlim(0), Some(0));
+        assert_eq!(iinlim(u16::MAX), Some(7));
+    }
+
+    #[test]
+    fn other_values_must_be_exact() {
+        assert_eq!(
+            quantized("VREG", Value::Quantity(4200, Unit::Milliamps)),
+            None
+        );
+        assert_eq!(quantized("CHG_CONFIG", Value::Named("otg")), Some(2));
+        assert_eq!(quantized("CHG_CONFIG", Value::Named("boost")), None);
+        assert_eq!(quantized("EN_HIZ", Value::Flag(true)), Some(1));
+    }
+}
@@ -0,0 +1,472 @@
+//! Complete charger configuration, buildable in `const` context.
+//!
+//! [`ChargerConfig`](struct.ChargerConfig.html) holds an image of the writable registers (0x00 - 0x07), starting from the power-on defaults.
+//! Every builder method is a `const fn`, so a product's configuration can be a `static` and any out of range or mis-stepped value is a compile error:
+//!
+//! ```
+//! use bq24195_i2c::config::ChargerConfig;
+//! use bq24195_i2c::{InputCurrentLimit, Watchdog};
+//!
+//! static CONFIG: ChargerConfig = ChargerConfig::new()
+//!     .input_current_limit(InputCurrentLimit::Milliamps1500)
+//!     .charge_current_milliamps(1024)
+//!     .charge_voltage_millivolts(4192)
+//!     .watchdog(Watchdog::Disabled);
+//!
+//! assert!(CONFIG.validate().is_empty());
+//! ```
+//!
+//! Ending the chain with [`assert_valid`](struct.ChargerConfig.html#method.assert_valid) also makes contradictory values, like a termination current above the charge current, a compile error.
+//!
+//! With the `macros` feature, `bq24195_config!` builds a configuration from physical values like `vreg = 4.192V` and checks it the same way.
+//!
+//! With the `serde` feature, a configuration can be stored in a file with each field named and decoded, or sent compactly with a format like postcard:
+//!
+//! ```
+//! # #[cfg(feature = "serde")]
+//! # fn main() {
+//! use bq24195_i2c::config::ChargerConfig;
+//!
+//! let config = ChargerConfig::new().charge_voltage_millivolts(4192);
+//! let json = serde_json::to_string(&config).unwrap();
+//! assert!(json.contains(r#""VREG":4192"#));
+//! assert_eq!(serde_json::from_str::<ChargerConfig>(&json).unwrap(), config);
+//!
+//! let bytes = postcard::to_allocvec(&config).unwrap();
+//! assert_eq!(bytes, config.registers());
+//! assert_eq!(postcard::from_bytes::<ChargerConfig>(&bytes).unwrap(), config);
+//! # }
+//! # #[cfg(not(feature = "serde"))]
+//! # fn main() {}
+//! ```
+//!
+//! The configuration is applied with [`ChargerState::write_config`](../struct.ChargerState.html#method.write_config).
+
+use crate::control::{
+    BatteryLowVoltage, ChargerMode, InputCurrentLimit, InterruptMask, RechargeThreshold,
+    SafetyTimer, ThermalRegulationThreshold, Watchdog, ICHG_MAX_MILLIAMPS, ICHG_MIN_MILLIAMPS,
+    ICHG_STEP_MILLIAMPS, IPRECHG_ITERM_MAX_MILLIAMPS, IPRECHG_ITERM_MIN_MILLIAMPS,
+    IPRECHG_ITERM_STEP_MILLIAMPS, SYS_MIN_MAX_MILLIVOLTS, SYS_MIN_MIN_MILLIVOLTS,
+    SYS_MIN_STEP_MILLIVOLTS, VINDPM_MAX_MILLIVOLTS, VINDPM_MIN_MILLIVOLTS, VINDPM_STEP_MILLIVOLTS,
+    VREG_MAX_MILLIVOLTS, VREG_MIN_MILLIVOLTS, VREG_STEP_MILLIVOLTS,
+};
+use crate::validate::{validate_registers, Limits, Violation, Violations};
+use crate::{
+    ChargeCurrentControl, ChargeTerminationTimerControl, ChargeVoltageControl, ChargerState,
+    InputSourceControl, MiscOperationControl, PowerOnConfiguration,
+    PreChargeTerminationCurrentControl, ThermalRegulationControl,
+};
+use hal::blocking::i2c::Write;
+
+/// Image of the writable registers (0x00 - 0x07)
+///
+/// With the `serde` feature, it is serialized like the matching registers of a [`ChargerState`](../struct.ChargerState.html).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "Registers", into = "Registers")
+)]
+pub struct ChargerConfig {
+    registers: [u8; 8],
+}
+
+/// Named view of [`ChargerConfig`] so every register is serialized with its fields
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Registers {
+    input_source_control: InputSourceControl,
+    power_on_configuration: PowerOnConfiguration,
+    charge_current_control: ChargeCurrentControl,
+    pre_charge_termination_current_control: PreChargeTerminationCurrentControl,
+    charge_voltage_control: ChargeVoltageControl,
+    charge_termination_timer_control: ChargeTerminationTimerControl,
+    thermal_regulation_control: ThermalRegulationControl,
+    misc_operation_control: MiscOperationControl,
+}
+
+#[cfg(feature = "serde")]
+impl From<ChargerConfig> for Registers {
+    fn from(config: ChargerConfig) -> Self {
+        let registers = config.registers;
+        Self {
+            input_source_control: registers[0x00].into(),
+            power_on_configuration: registers[0x01].into(),
+            charge_current_control: registers[0x02].into(),
+            pre_charge_termination_current_control: registers[0x03].into(),
+            charge_voltage_control: registers[0x04].into(),
+            charge_termination_timer_control: registers[0x05].into(),
+            thermal_regulation_control: registers[0x06].into(),
+            misc_operation_control: registers[0x07].into(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Registers> for ChargerConfig {
+    fn from(registers: Registers) -> Self {
+        Self::from_registers([
+            registers.input_source_control.bits(),
+            registers.power_on_configuration.bits(),
+            registers.charge_current_control.bits(),
+            registers.pre_charge_termination_current_control.bits(),
+            registers.charge_voltage_control.bits(),
+            registers.charge_termination_timer_control.bits(),
+            registers.thermal_regulation_control.bits(),
+            registers.misc_operation_control.bits(),
+        ])
+    }
+}
+
+/// Every register, decoding every field
+#[cfg(feature = "defmt")]
+impl defmt::Format for ChargerConfig {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        crate::field::format_registers(f, &self.registers)
+    }
+}
+
+impl Default for ChargerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChargerConfig {
+    /// Configuration with the power-on default of every register
+    pub const fn new() -> Self {
+        Self {
+            registers: [
+                InputSourceControl::DEFAULT.bits(),
+                PowerOnConfiguration::DEFAULT.bits(),
+                ChargeCurrentControl::DEFAULT.bits(),
+                PreChargeTerminationCurrentControl::DEFAULT.bits(),
+                ChargeVoltageControl::DEFAULT.bits(),
+                ChargeTerminationTimerControl::DEFAULT.bits(),
+                ThermalRegulationControl::DEFAULT.bits(),
+                MiscOperationControl::DEFAULT.bits(),
+            ],
+        }
+    }
+
+    /// Configuration from an image of the writable registers. Reserved bits are taken as given.
+    pub const fn from_registers(registers: [u8; 8]) -> Self {
+        Self { registers }
+    }
+
+    /// Image of the writable registers (0x00 - 0x07)
+    pub const fn registers(&self) -> [u8; 8] {
+        self.registers
+    }
+
+    /// Check the configuration against the datasheet constraints, see [`ChargerState::validate`](../struct.ChargerState.html#method.validate)
+    pub fn validate(&self) -> Violations {
+        self.validate_with(&Limits::default())
+    }
+
+    /// Check the configuration against the datasheet constraints and the given board and cell limits, see [`ChargerState::validate_with`](../struct.ChargerState.html#method.validate_with)
+    pub fn validate_with(&self, limits: &Limits) -> Violations {
+        validate_registers(&self.registers, limits)
+    }
+
+    /// Check the configuration against the datasheet constraints like [`validate`](#method.validate), and return it unchanged.
+    ///
+    /// Meant to end a `const` builder chain, so that a configuration with contradictory values is a compile error:
+    ///
+    /// ```compile_fail
+    /// # use bq24195_i2c::config::ChargerConfig;
+    /// // SYS_MIN must be below VREG
+    /// static CONFIG: ChargerConfig = ChargerConfig::new()
+    ///     .system_minimum_millivolts(3700)
+    ///     .charge_voltage_millivolts(3600)
+    ///     .assert_valid();
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If any constraint is broken, with the description of the first [`Violation`](../validate/enum.Violation.html).
+    pub const fn assert_valid(self) -> Self {
+        let limits = Limits {
+            r_ilim_ohms: None,
+            max_charge_voltage_mv: None,
+        };
+        let violations = validate_registers(&self.registers, &limits);
+        let mut i = 0;
+        while i < Violation::ALL.len() {
+            if violations.contains(Violation::ALL[i]) {
+                panic!("{}", Violation::ALL[i].description());
+            }
+            i += 1;
+        }
+        self
+    }
+
+    const fn field(mut self, register: usize, mask: u8, value: u8) -> Self {
+        self.registers[register] = (self.registers[register] & !mask) | (value & mask);
+        self
+    }
+
+    const fn flag(self, register: usize, bit: u8, set: bool) -> Self {
+        self.field(register, 1 << bit, if set { 0xFF } else { 0 })
+    }
+
+    /// Stop the buck converter and supply the system from the battery (EN_HIZ)
+    pub const fn hiz(self, hiz: bool) -> Self {
+        self.flag(0x00, 7, hiz)
+    }
+
+    /// Input voltage limit (VINDPM) in mV.
+    ///
+    /// # Panics
+    ///
+    /// If the limit is not between 3880mV and 5080mV in 80mV steps.
+    pub const fn vindpm_millivolts(self, millivolts: u16) -> Self {
+        match linear(
+            millivolts,
+            VINDPM_MIN_MILLIVOLTS,
+            VINDPM_MAX_MILLIVOLTS,
+            VINDPM_STEP_MILLIVOLTS,
+        ) {
+            Some(raw) => self.field(0x00, 0b0111_1000, raw << 3),
+            None => panic!("VINDPM must be between 3880mV and 5080mV in 80mV steps"),
+        }
+    }
+
+    /// Input current limit (IINLIM)
+    pub const fn input_current_limit(self, limit: InputCurrentLimit) -> Self {
+        self.field(0x00, 0b0000_0111, limit.bits())
+    }
+
+    /// Charger configuration (CHG_CONFIG)
+    pub const fn charger_mode(self, mode: ChargerMode) -> Self {
+        self.field(0x01, 0b0011_0000, mode.bits() << 4)
+    }
+
+    /// Minimum system voltage limit (SYS_MIN) in mV.
+    ///
+    /// # Panics
+    ///
+    /// If the limit is not between 3000mV and 3700mV in 100mV steps.
+    pub const fn system_minimum_millivolts(self, millivolts: u16) -> Self {
+        match linear(
+            millivolts,
+            SYS_MIN_MIN_MILLIVOLTS,
+            SYS_MIN_MAX_MILLIVOLTS,
+            SYS_MIN_STEP_MILLIVOLTS,
+        ) {
+            Some(raw) => self.field(0x01, 0b0000_1110, raw << 1),
+            None => panic!("SYS_MIN must be between 3000mV and 3700mV in 100mV steps"),
+        }
+    }
+
+    /// Fast charge current limit (ICHG) in mA, before [`force_20pct`](#method.force_20pct) is applied.
+    ///
+    /// # Panics
+    ///
+    /// If the limit is not between 512mA and 4544mA in 64mA steps.
+    pub const fn charge_current_milliamps(self, milliamps: u16) -> Self {
+        match linear(
+            milliamps,
+            ICHG_MIN_MILLIAMPS,
+            ICHG_MAX_MILLIAMPS,
+            ICHG_STEP_MILLIAMPS,
+        ) {
+            Some(raw) => self.field(0x02, 0b1111_1100, raw << 2),
+            None => panic!("ICHG must be between 512mA and 4544mA in 64mA steps"),
+        }
+    }
+
+    /// Use 20% of the fast charge current limit and 50% of the pre-charge current limit (FORCE_20PCT)
+    pub const fn force_20pct(self, force: bool) -> Self {
+        self.flag(0x02, 0, force)
+    }
+
+    /// Pre-charge current limit (IPRECHG) in mA, before [`force_20pct`](#method.force_20pct) is applied.
+    ///
+    /// # Panics
+    ///
+    /// If the limit is not between 128mA and 2048mA in 128mA steps.
+    pub const fn precharge_current_milliamps(self, milliamps: u16) -> Self {
+        match linear(
+            milliamps,
+            IPRECHG_ITERM_MIN_MILLIAMPS,
+            IPRECHG_ITERM_MAX_MILLIAMPS,
+            IPRECHG_ITERM_STEP_MILLIAMPS,
+        ) {
+            Some(raw) => self.field(0x03, 0b1111_0000, raw << 4),
+            None => panic!("IPRECHG must be between 128mA and 2048mA in 128mA steps"),
+        }
+    }
+
+    /// Termination current limit (ITERM) in mA.
+    ///
+    /// # Panics
+    ///
+    /// If the limit is not between 128mA and 2048mA in 128mA steps.
+    pub const fn termination_current_milliamps(self, milliamps: u16) -> Self {
+        match linear(
+            milliamps,
+            IPRECHG_ITERM_MIN_MILLIAMPS,
+            IPRECHG_ITERM_MAX_MILLIAMPS,
+            IPRECHG_ITERM_STEP_MILLIAMPS,
+        ) {
+            Some(raw) => self.field(0x03, 0b0000_1111, raw),
+            None => panic!("ITERM must be between 128mA and 2048mA in 128mA steps"),
+        }
+    }
+
+    /// Charge voltage limit (VREG) in mV.
+    ///
+    /// # Panics
+    ///
+    /// If the limit is not between 3504mV and 4400mV in 16mV steps.
+    pub const fn charge_voltage_millivolts(self, millivolts: u16) -> Self {
+        match linear(
+            millivolts,
+            VREG_MIN_MILLIVOLTS,
+            VREG_MAX_MILLIVOLTS,
+            VREG_STEP_MILLIVOLTS,
+        ) {
+            Some(raw) => self.field(0x04, 0b1111_1100, raw << 2),
+            None => panic!("VREG must be between 3504mV and 4400mV in 16mV steps"),
+        }
+    }
+
+    /// Battery pre-charge to fast charge threshold (BATLOWV)
+    pub const fn battery_low_voltage(self, threshold: BatteryLowVoltage) -> Self {
+        self.flag(
+            0x04,
+            1,
+            matches!(threshold, BatteryLowVoltage::Millivolts3000),
+        )
+    }
+
+    /// Battery recharge threshold (VRECHG)
+    pub const fn recharge_threshold(self, threshold: RechargeThreshold) -> Self {
+        self.flag(
+            0x04,
+            0,
+            matches!(threshold, RechargeThreshold::Millivolts300),
+        )
+    }
+
+    /// Terminate charging once the current drops below the termination current limit (EN_TERM)
+    pub const fn termination(self, enabled: bool) -> Self {
+        self.flag(0x05, 7, enabled)
+    }
+
+    /// I2C watchdog timer (WATCHDOG)
+    pub const fn watchdog(self, watchdog: Watchdog) -> Self {
+        self.field(0x05, 0b0011_0000, watchdog.bits() << 4)
+    }
+
+    /// Fast charge safety timer (CHG_TIMER), or `None` to disable it (EN_TIMER)
+    pub const fn safety_timer(self, timer: Option<SafetyTimer>) -> Self {
+        match timer {
+            Some(timer) => self.field(0x05, 0b0000_1110, 0b1000 | (timer.bits() << 1)),
+            None => self.flag(0x05, 3, false),
+        }
+    }
+
+    /// Slow the safety timer by 2x during input DPM and thermal regulation (TMR2X_EN)
+    pub const fn safety_timer_2x(self, enabled: bool) -> Self {
+        self.flag(0x07, 6, enabled)
+    }
+
+    /// Thermal regulation threshold (TREG)
+    pub const fn thermal_regulation_threshold(self, threshold: ThermalRegulationThreshold) -> Self {
+        self.field(0x06, 0b0000_0011, threshold.bits())
+    }
+
+    /// Faults that pulse the INT pin (INT_MASK)
+    pub const fn interrupt_mask(self, mask: InterruptMask) -> Self {
+        self.flag(0x07, 1, mask.charge_fault)
+            .flag(0x07, 0, mask.battery_fault)
+    }
+}
+
+/// Raw value of a linear field, or `None` if `value` is not `min` plus a multiple of `step` up to `max`
+// `is_multiple_of` needs Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+const fn linear(value: u16, min: u16, max: u16, step: u16) -> Option<u8> {
+    if value < min || value > max || (value - min) % step != 0 {
+        None
+    } else {
+        Some(((value - min) / step) as u8)
+    }
+}
+
+impl ChargerState {
+    /// Write a configuration to all writable registers with [`write_all`](#method.write_all), then apply it to the chip state.
+    /// Staged edits to those registers are replaced.
+    ///
+    /// If an error occurs, the chip state remains the same.
+    pub fn write_config<E, I2C: Write<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        config: &ChargerConfig,
+    ) -> Result<(), E> {
+        let registers = config.registers();
+        let mut next = self.clone();
+        next.input_source_control = registers[0x00].into();
+        next.power_on_configuration = registers[0x01].into();
+        next.charge_current_control = registers[0x02].into();
+        next.pre_charge_termination_current_control = registers[0x03].into();
+        next.charge_voltage_control = registers[0x04].into();
+        next.charge_termination_timer_control = registers[0x05].into();
+        next.thermal_regulation_control = registers[0x06].into();
+        next.misc_operation_control = registers[0x07].into();
+        next.write_all(i2c)?;
+        *self = next;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bus on which every transaction fails
+    struct Unplugged;
+
+    impl Write for Unplugged {
+        type Error = ();
+
+        fn write(&mut self, _: u8, _: &[u8]) -> Result<(), ()> {
+            Err(())
+        }
+    }
+
+    #[test]
+    fn defaults_match_datasheet() {
+        assert_eq!(
+            ChargerConfig::new().registers(),
+            [0x30, 0x1B, 0x60, 0x11, 0xB2, 0x9A, 0x03, 0x4B]
+        );
+    }
+
+    #[test]
+    fn assert_valid_passes_valid_config() {
+        const CONFIG: ChargerConfig = ChargerConfig::new()
+            .charge_current_milliamps(1024)
+            .assert_valid();
+        assert_eq!(CONFIG, ChargerConfig::new().charge_current_milliamps(1024));
+    }
+
+    #[test]
+    #[should_panic(expected = "the pre-charge current is above the fast charge current")]
+    fn assert_valid_rejects_contradiction() {
+        ChargerConfig::new()
+            .charge_current_milliamps(512)
+            .precharge_current_milliamps(640)
+            .assert_valid();
+    }
+
+    #[test]
+    fn write_config_error_keeps_state() {
+        let mut state = ChargerState::from_registers([0; 11]);
+        let config = ChargerConfig::new().charge_voltage_millivolts(4192);
+        assert_eq!(state.write_config(&mut Unplugged, &config), Err(()));
+        assert_eq!(state.registers(), [0; 11]);
+    }
+}
@@ -0,0 +1,833 @@
+//! Typed views of fields in the writable control registers (0x00 - 0x07).
+
+use crate::{
+    ChargeCurrentControl, ChargeStatus, ChargeTerminationTimerControl, ChargeVoltageControl,
+    ChargerState, InputSourceControl, MiscOperationControl, PowerOnConfiguration,
+    PreChargeTerminationCurrentControl, ThermalRegulationControl,
+};
+use hal::blocking::i2c::{Write, WriteRead};
+
+/// Faults that pulse the INT pin, set in [`MiscOperationControl::INT_MASK[1:0]`](../struct.MiscOperationControl.html#associatedconstant.INT_MASK_1)
+///
+/// Status changes in [`SystemStatus`](../struct.SystemStatus.html) always pulse INT; only faults can be masked.
+/// A masked fault is still latched in [`Fault`](../struct.Fault.html) and will be seen on the next read.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterruptMask {
+    /// Pulse INT on [`Fault::CHRG_FAULT[1:0]`](../struct.Fault.html#associatedconstant.CHRG_FAULT_1)
+    pub charge_fault: bool,
+    /// Pulse INT on [`Fault::BAT_FAULT`](../struct.Fault.html#associatedconstant.BAT_FAULT)
+    pub battery_fault: bool,
+}
+
+impl Default for InterruptMask {
+    /// Both faults pulse INT, as they do at power on
+    fn default() -> Self {
+        Self {
+            charge_fault: true,
+            battery_fault: true,
+        }
+    }
+}
+
+impl MiscOperationControl {
+    /// Faults that will pulse the INT pin
+    pub fn interrupt_mask(&self) -> InterruptMask {
+        InterruptMask {
+            charge_fault: self.contains(Self::INT_MASK_1),
+            battery_fault: self.contains(Self::INT_MASK_0),
+        }
+    }
+
+    /// Change which faults will pulse the INT pin, leaving the other fields untouched
+    pub fn set_interrupt_mask(&mut self, mask: InterruptMask) {
+        *self &= !(Self::INT_MASK_1 | Self::INT_MASK_0);
+        if mask.charge_fault {
+            *self |= Self::INT_MASK_1;
+        }
+        if mask.battery_fault {
+            *self |= Self::INT_MASK_0;
+        }
+    }
+}
+
+impl ChargerState {
+    /// Get the interrupt mask from the current chip state. Does NOT do an I2C call.
+    pub fn get_interrupt_mask(&self) -> InterruptMask {
+        self.get_misc_operation_control().interrupt_mask()
+    }
+
+    /// Read [`MiscOperationControl`](struct.MiscOperationControl.html), change only the interrupt mask, and write it back.
+    ///
+    /// [`MiscOperationControl::DPDM_EN`](struct.MiscOperationControl.html#associatedconstant.DPDM_EN) is cleared so that an in-progress detection is not restarted.
+    pub fn write_interrupt_mask<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        mask: InterruptMask,
+    ) -> Result<(), E> {
+        self.read_misc_operation_control(i2c)?;
+        let mut misc_operation_control =
+            self.get_misc_operation_control() & !MiscOperationControl::DPDM_EN;
+        misc_operation_control.set_interrupt_mask(mask);
+        self.write_misc_operation_control(i2c, misc_operation_control)
+    }
+}
+
+/// Fast charge safety timer duration, set in [`ChargeTerminationTimerControl::CHG_TIMER[2:1]`](../struct.ChargeTerminationTimerControl.html#associatedconstant.CHG_TIMER_1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SafetyTimer {
+    /// 5 hours (00)
+    Hours5,
+    /// 8 hours (01)
+    Hours8,
+    /// 12 hours (10)
+    Hours12,
+    /// 20 hours (11)
+    Hours20,
+}
+
+impl SafetyTimer {
+    /// Nominal duration of the timer in hours
+    pub const fn hours(self) -> u8 {
+        match self {
+            SafetyTimer::Hours5 => 5,
+            SafetyTimer::Hours8 => 8,
+            SafetyTimer::Hours12 => 12,
+            SafetyTimer::Hours20 => 20,
+        }
+    }
+
+    /// Value of the `CHG_TIMER[2:1]` field
+    pub const fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+impl ChargeTerminationTimerControl {
+    /// Fast charge safety timer duration
+    pub fn safety_timer(&self) -> SafetyTimer {
+        match (self.bits() >> 1) & 0b11 {
+            0b00 => SafetyTimer::Hours5,
+            0b01 => SafetyTimer::Hours8,
+            0b10 => SafetyTimer::Hours12,
+            _ => SafetyTimer::Hours20,
+        }
+    }
+
+    /// Change the fast charge safety timer duration, leaving the other fields untouched.
+    ///
+    /// The datasheet recommends disabling the timer while changing it, see [`ChargerState::set_safety_timer`](struct.ChargerState.html#method.set_safety_timer).
+    pub fn set_safety_timer(&mut self, timer: SafetyTimer) {
+        let value = timer.bits();
+        *self = Self::from((self.bits() & !0b0000_0110) | (value << 1));
+    }
+
+    /// True if the fast charge safety timer is enabled
+    pub fn safety_timer_enabled(&self) -> bool {
+        self.contains(Self::EN_TIMER)
+    }
+}
+
+impl ChargerState {
+    /// Change the safety timer duration following the datasheet procedure: disable the timer, write the new duration, then re-enable it.
+    ///
+    /// If the timer is currently disabled, only the duration is written and it stays disabled.
+    pub fn set_safety_timer<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        timer: SafetyTimer,
+    ) -> Result<(), E> {
+        self.read_charge_termination_timer_control(i2c)?;
+        let current = self.get_charge_termination_timer_control();
+        let enabled = current.safety_timer_enabled();
+        let mut disabled = current & !ChargeTerminationTimerControl::EN_TIMER;
+        if enabled {
+            self.write_charge_termination_timer_control(i2c, disabled)?;
+        }
+        disabled.set_safety_timer(timer);
+        self.write_charge_termination_timer_control(i2c, disabled)?;
+        if enabled {
+            self.write_charge_termination_timer_control(
+                i2c,
+                disabled | ChargeTerminationTimerControl::EN_TIMER,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Restart the safety timer by disabling and re-enabling it. Does nothing if the timer is disabled.
+    pub fn restart_safety_timer<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+    ) -> Result<(), E> {
+        self.read_charge_termination_timer_control(i2c)?;
+        let current = self.get_charge_termination_timer_control();
+        if current.safety_timer_enabled() {
+            self.write_charge_termination_timer_control(
+                i2c,
+                current & !ChargeTerminationTimerControl::EN_TIMER,
+            )?;
+            self.write_charge_termination_timer_control(i2c, current)?;
+        }
+        Ok(())
+    }
+
+    /// Read registers 0x00 - 0x08 and compute how long the safety timer will actually take in minutes, or `None` if it is disabled.
+    ///
+    /// With a 100mA input current limit (USB100) the timer is always 45 minutes, and while the battery is below BATLOWV (pre-charge) it is 1 hour.
+    /// Otherwise it is the programmed [`SafetyTimer`](control/enum.SafetyTimer.html) duration, which is doubled when
+    /// [`MiscOperationControl::TMR2X_EN`](struct.MiscOperationControl.html#associatedconstant.TMR2X_EN) is set and the charger is in input DPM, thermal regulation,
+    /// or [`ChargeCurrentControl::FORCE_20PCT`](struct.ChargeCurrentControl.html#associatedconstant.FORCE_20PCT) is set.
+    ///
+    /// Staged edits to the registers read are discarded.
+    pub fn effective_safety_timer<E, I2C: WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+    ) -> Result<Option<u16>, E> {
+        self.read_range(i2c, 0x00..=0x08)?;
+        let timer_control = self.get_charge_termination_timer_control();
+        if !timer_control.safety_timer_enabled() {
+            return Ok(None);
+        }
+        if self.get_input_source_control().input_current_limit() == InputCurrentLimit::Milliamps100
+        {
+            return Ok(Some(45));
+        }
+        let status = self.get_system_status();
+        if status.charge_status() == ChargeStatus::PreCharge {
+            return Ok(Some(60));
+        }
+        let slowed = self
+            .get_misc_operation_control()
+            .contains(MiscOperationControl::TMR2X_EN)
+            && (status.dpm()
+                || status.thermal_regulation()
+                || self
+                    .get_charge_current_control()
+                    .contains(ChargeCurrentControl::FORCE_20PCT));
+        let minutes = u16::from(timer_control.safety_timer().hours()) * 60;
+        Ok(Some(if slowed { minutes * 2 } else { minutes }))
+    }
+}
+
+/// Charger configuration, set in [`PowerOnConfiguration::CHG_CONFIG[1:0]`](../struct.PowerOnConfiguration.html#associatedconstant.CHG_CONFIG_1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChargerMode {
+    /// Charging disabled (00)
+    ChargeDisabled,
+    /// Charge battery (01)
+    Charge,
+    /// OTG boost (10 or 11)
+    Otg,
+}
+
+impl ChargerMode {
+    /// Value of the `CHG_CONFIG[1:0]` field. OTG boost is written as 10.
+    pub const fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+impl PowerOnConfiguration {
+    /// Charger configuration
+    pub fn charger_mode(&self) -> ChargerMode {
+        match (self.bits() >> 4) & 0b11 {
+            0b00 => ChargerMode::ChargeDisabled,
+            0b01 => ChargerMode::Charge,
+            _ => ChargerMode::Otg,
+        }
+    }
+
+    /// Change the charger configuration, leaving the other fields untouched
+    pub fn set_charger_mode(&mut self, mode: ChargerMode) {
+        let value = mode.bits();
+        *self = Self::from((self.bits() & !0b0011_0000) | (value << 4));
+    }
+}
+
+impl ChargerState {
+    /// Read [`PowerOnConfiguration`](struct.PowerOnConfiguration.html), change only the charger configuration, and write it back.
+    /// Returns the previous charger configuration.
+    ///
+    /// The minimum system voltage is preserved, the reserved bit is written as 1, and the register and watchdog reset bits are written as 0.
+    pub fn write_charger_mode<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        mode: ChargerMode,
+    ) -> Result<ChargerMode, E> {
+        self.read_power_on_configuration(i2c)?;
+        let current = self.get_power_on_configuration();
+        let mut power_on_configuration = (current
+            & !(PowerOnConfiguration::REGISTER_RESET
+                | PowerOnConfiguration::I2C_WATCHDOG_TIMER_RESET))
+            | PowerOnConfiguration::RESERVED;
+        power_on_configuration.set_charger_mode(mode);
+        self.write_power_on_configuration(i2c, power_on_configuration)?;
+        Ok(current.charger_mode())
+    }
+
+    /// Enable battery charging. Returns the previous charger configuration.
+    pub fn enable_charging<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+    ) -> Result<ChargerMode, E> {
+        self.write_charger_mode(i2c, ChargerMode::Charge)
+    }
+
+    /// Disable battery charging. Returns the previous charger configuration.
+    pub fn disable_charging<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+    ) -> Result<ChargerMode, E> {
+        self.write_charger_mode(i2c, ChargerMode::ChargeDisabled)
+    }
+
+    /// Enable OTG boost, supplying VBUS from the battery. Returns the previous charger configuration.
+    pub fn enable_otg<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+    ) -> Result<ChargerMode, E> {
+        self.write_charger_mode(i2c, ChargerMode::Otg)
+    }
+}
+
+/// I2C watchdog timer, set in [`ChargeTerminationTimerControl::WATCHDOG[1:0]`](../struct.ChargeTerminationTimerControl.html#associatedconstant.WATCHDOG_1)
+///
+/// When the watchdog expires, the chip returns to default mode and the registers are reset to their defaults.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Watchdog {
+    /// Watchdog disabled, the chip stays in host mode (00)
+    Disabled,
+    /// 40 seconds (01)
+    Seconds40,
+    /// 80 seconds (10)
+    Seconds80,
+    /// 160 seconds (11)
+    Seconds160,
+}
+
+impl Watchdog {
+    /// Value of the `WATCHDOG[1:0]` field
+    pub const fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+impl ChargeTerminationTimerControl {
+    /// I2C watchdog timer setting
+    pub fn watchdog(&self) -> Watchdog {
+        match (self.bits() >> 4) & 0b11 {
+            0b00 => Watchdog::Disabled,
+            0b01 => Watchdog::Seconds40,
+            0b10 => Watchdog::Seconds80,
+            _ => Watchdog::Seconds160,
+        }
+    }
+
+    /// Change the I2C watchdog timer setting, leaving the other fields untouched
+    pub fn set_watchdog(&mut self, watchdog: Watchdog) {
+        let value = watchdog.bits();
+        *self = Self::from((self.bits() & !0b0011_0000) | (value << 4));
+    }
+}
+
+impl ChargerState {
+    /// Read [`ChargeTerminationTimerControl`](struct.ChargeTerminationTimerControl.html), change only the watchdog timer setting, and write it back
+    pub fn write_watchdog<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        watchdog: Watchdog,
+    ) -> Result<(), E> {
+        self.read_charge_termination_timer_control(i2c)?;
+        let mut charge_termination_timer_control = self.get_charge_termination_timer_control();
+        charge_termination_timer_control.set_watchdog(watchdog);
+        self.write_charge_termination_timer_control(i2c, charge_termination_timer_control)
+    }
+}
+
+/// Input current limit, set in [`InputSourceControl::IINLIM[2:0]`](../struct.InputSourceControl.html#associatedconstant.IINLIM_2)
+///
+/// The ILIM pin also limits input current. This can only reduce the limit below ILIM.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputCurrentLimit {
+    /// 100 mA (000)
+    Milliamps100,
+    /// 150 mA (001)
+    Milliamps150,
+    /// 500 mA (010)
+    Milliamps500,
+    /// 900 mA (011)
+    Milliamps900,
+    /// 1200 mA (100)
+    Milliamps1200,
+    /// 1500 mA (101)
+    Milliamps1500,
+    /// 2000 mA (110)
+    Milliamps2000,
+    /// 3000 mA (111)
+    Milliamps3000,
+}
+
+impl InputCurrentLimit {
+    /// All limits, from lowest to highest
+    pub const ALL: [InputCurrentLimit; 8] = [
+        InputCurrentLimit::Milliamps100,
+        InputCurrentLimit::Milliamps150,
+        InputCurrentLimit::Milliamps500,
+        InputCurrentLimit::Milliamps900,
+        InputCurrentLimit::Milliamps1200,
+        InputCurrentLimit::Milliamps1500,
+        InputCurrentLimit::Milliamps2000,
+        InputCurrentLimit::Milliamps3000,
+    ];
+
+    /// Limit in mA
+    pub const fn milliamps(self) -> u16 {
+        match self {
+            InputCurrentLimit::Milliamps100 => 100,
+            InputCurrentLimit::Milliamps150 => 150,
+            InputCurrentLimit::Milliamps500 => 500,
+            InputCurrentLimit::Milliamps900 => 900,
+            InputCurrentLimit::Milliamps1200 => 1200,
+            InputCurrentLimit::Milliamps1500 => 1500,
+            InputCurrentLimit::Milliamps2000 => 2000,
+            InputCurrentLimit::Milliamps3000 => 3000,
+        }
+    }
+
+    /// Value of the `IINLIM[2:0]` field
+    pub const fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Limit for a value of the `IINLIM[2:0]` field. Only the lowest 3 bits are used.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self::ALL[(bits & 0b111) as usize]
+    }
+}
+
+impl InputSourceControl {
+    /// Input current limit
+    pub const fn input_current_limit(&self) -> InputCurrentLimit {
+        InputCurrentLimit::from_bits(self.bits())
+    }
+
+    /// Change the input current limit, leaving the other fields untouched
+    pub fn set_input_current_limit(&mut self, limit: InputCurrentLimit) {
+        *self = Self::from((self.bits() & !0b0000_0111) | limit.bits());
+    }
+}
+
+impl ChargerState {
+    /// Read [`InputSourceControl`](struct.InputSourceControl.html), change only the input current limit, and write it back
+    pub fn write_input_current_limit<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        limit: InputCurrentLimit,
+    ) -> Result<(), E> {
+        self.read_input_source_control(i2c)?;
+        let mut input_source_control = self.get_input_source_control();
+        input_source_control.set_input_current_limit(limit);
+        self.write_input_source_control(i2c, input_source_control)
+    }
+}
+
+/// Lowest input voltage limit in mV, when `VINDPM[3:0]` = 0000
+pub const VINDPM_MIN_MILLIVOLTS: u16 = 3880;
+/// Highest input voltage limit in mV, when `VINDPM[3:0]` = 1111
+pub const VINDPM_MAX_MILLIVOLTS: u16 = 5080;
+/// Input voltage limit step size in mV
+pub const VINDPM_STEP_MILLIVOLTS: u16 = 80;
+
+impl InputSourceControl {
+    /// Input voltage limit in mV
+    pub fn vindpm_millivolts(&self) -> u16 {
+        VINDPM_MIN_MILLIVOLTS + u16::from((self.bits() >> 3) & 0b1111) * VINDPM_STEP_MILLIVOLTS
+    }
+
+    /// Change the input voltage limit, leaving the other fields untouched.
+    ///
+    /// The limit is clamped to 3.88V - 5.08V and rounded down to the nearest 80mV step.
+    pub fn set_vindpm_millivolts(&mut self, millivolts: u16) {
+        let offset = (millivolts.clamp(VINDPM_MIN_MILLIVOLTS, VINDPM_MAX_MILLIVOLTS)
+            - VINDPM_MIN_MILLIVOLTS)
+            / VINDPM_STEP_MILLIVOLTS;
+        *self = Self::from((self.bits() & !0b0111_1000) | ((offset as u8) << 3));
+    }
+}
+
+impl ChargerState {
+    /// Read [`InputSourceControl`](struct.InputSourceControl.html), change only the input voltage limit, and write it back
+    pub fn write_vindpm_millivolts<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        millivolts: u16,
+    ) -> Result<(), E> {
+        self.read_input_source_control(i2c)?;
+        let mut input_source_control = self.get_input_source_control();
+        input_source_control.set_vindpm_millivolts(millivolts);
+        self.write_input_source_control(i2c, input_source_control)
+    }
+}
+
+/// Lowest fast charge current limit in mA, when `ICHG[5:0]` = 000000
+pub const ICHG_MIN_MILLIAMPS: u16 = 512;
+/// Highest fast charge current limit in mA, when `ICHG[5:0]` = 111111
+pub const ICHG_MAX_MILLIAMPS: u16 = 4544;
+/// Fast charge current limit step size in mA
+pub const ICHG_STEP_MILLIAMPS: u16 = 64;
+
+impl ChargeCurrentControl {
+    /// Fast charge current limit in mA, ignoring [`FORCE_20PCT`](#associatedconstant.FORCE_20PCT)
+    pub const fn charge_current_milliamps(&self) -> u16 {
+        ICHG_MIN_MILLIAMPS + (self.bits() >> 2) as u16 * ICHG_STEP_MILLIAMPS
+    }
+
+    /// Change the fast charge current limit, leaving the other fields untouched.
+    ///
+    /// The limit is clamped to 512mA - 4544mA and rounded down to the nearest 64mA step.
+    pub fn set_charge_current_milliamps(&mut self, milliamps: u16) {
+        let offset = (milliamps.clamp(ICHG_MIN_MILLIAMPS, ICHG_MAX_MILLIAMPS) - ICHG_MIN_MILLIAMPS)
+            / ICHG_STEP_MILLIAMPS;
+        *self = Self::from((self.bits() & !0b1111_1100) | ((offset as u8) << 2));
+    }
+
+    /// Fast charge current that will actually be used in mA, taking [`FORCE_20PCT`](#associatedconstant.FORCE_20PCT) into account
+    pub const fn effective_charge_current_milliamps(&self) -> u16 {
+        if self.contains(Self::FORCE_20PCT) {
+            self.charge_current_milliamps() / 5
+        } else {
+            self.charge_current_milliamps()
+        }
+    }
+}
+
+/// Lowest pre-charge or termination current limit in mA, when `IPRECHG[3:0]` or `ITERM[3:0]` = 0000
+pub const IPRECHG_ITERM_MIN_MILLIAMPS: u16 = 128;
+/// Highest pre-charge or termination current limit in mA, when `IPRECHG[3:0]` or `ITERM[3:0]` = 1111
+pub const IPRECHG_ITERM_MAX_MILLIAMPS: u16 = 2048;
+/// Pre-charge and termination current limit step size in mA
+pub const IPRECHG_ITERM_STEP_MILLIAMPS: u16 = 128;
+
+/// Offset of a pre-charge or termination current, clamped and rounded down to the nearest step
+fn iprechg_iterm_offset(milliamps: u16) -> u8 {
+    ((milliamps.clamp(IPRECHG_ITERM_MIN_MILLIAMPS, IPRECHG_ITERM_MAX_MILLIAMPS)
+        - IPRECHG_ITERM_MIN_MILLIAMPS)
+        / IPRECHG_ITERM_STEP_MILLIAMPS) as u8
+}
+
+impl PreChargeTerminationCurrentControl {
+    /// Pre-charge current limit in mA, ignoring [`ChargeCurrentControl::FORCE_20PCT`](struct.ChargeCurrentControl.html#associatedconstant.FORCE_20PCT)
+    pub const fn precharge_current_milliamps(&self) -> u16 {
+        IPRECHG_ITERM_MIN_MILLIAMPS + (self.bits() >> 4) as u16 * IPRECHG_ITERM_STEP_MILLIAMPS
+    }
+
+    /// Change the pre-charge current limit, leaving the other fields untouched.
+    ///
+    /// The limit is clamped to 128mA - 2048mA and rounded down to the nearest 128mA step.
+    pub fn set_precharge_current_milliamps(&mut self, milliamps: u16) {
+        *self = Self::from((self.bits() & 0b0000_1111) | (iprechg_iterm_offset(milliamps) << 4));
+    }
+
+    /// Termination current limit in mA
+    pub const fn termination_current_milliamps(&self) -> u16 {
+        IPRECHG_ITERM_MIN_MILLIAMPS + (self.bits() & 0b1111) as u16 * IPRECHG_ITERM_STEP_MILLIAMPS
+    }
+
+    /// Change the termination current limit, leaving the other fields untouched.
+    ///
+    /// The limit is clamped to 128mA - 2048mA and rounded down to the nearest 128mA step.
+    pub fn set_termination_current_milliamps(&mut self, milliamps: u16) {
+        *self = Self::from((self.bits() & 0b1111_0000) | iprechg_iterm_offset(milliamps));
+    }
+}
+
+/// Lowest charge voltage limit in mV, when `VREG[5:0]` = 000000
+pub const VREG_MIN_MILLIVOLTS: u16 = 3504;
+/// Highest charge voltage limit in mV supported by the datasheet. `VREG[5:0]` can encode up to 4512mV, but anything above 4400mV is out of spec.
+pub const VREG_MAX_MILLIVOLTS: u16 = 4400;
+/// Charge voltage limit step size in mV
+pub const VREG_STEP_MILLIVOLTS: u16 = 16;
+
+/// Battery pre-charge to fast charge threshold, set in [`ChargeVoltageControl::BATLOWV`](../struct.ChargeVoltageControl.html#associatedconstant.BATLOWV)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BatteryLowVoltage {
+    /// 2.8V (0)
+    Millivolts2800,
+    /// 3.0V (1)
+    Millivolts3000,
+}
+
+impl BatteryLowVoltage {
+    /// Threshold in mV
+    pub const fn millivolts(self) -> u16 {
+        match self {
+            BatteryLowVoltage::Millivolts2800 => 2800,
+            BatteryLowVoltage::Millivolts3000 => 3000,
+        }
+    }
+}
+
+/// Battery recharge threshold below the charge voltage limit, set in [`ChargeVoltageControl::VRECHG`](../struct.ChargeVoltageControl.html#associatedconstant.VRECHG)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RechargeThreshold {
+    /// 100mV below VREG (0)
+    Millivolts100,
+    /// 300mV below VREG (1)
+    Millivolts300,
+}
+
+impl RechargeThreshold {
+    /// Threshold below VREG in mV
+    pub const fn millivolts(self) -> u16 {
+        match self {
+            RechargeThreshold::Millivolts100 => 100,
+            RechargeThreshold::Millivolts300 => 300,
+        }
+    }
+}
+
+impl ChargeVoltageControl {
+    /// Charge voltage limit in mV
+    pub const fn charge_voltage_millivolts(&self) -> u16 {
+        VREG_MIN_MILLIVOLTS + (self.bits() >> 2) as u16 * VREG_STEP_MILLIVOLTS
+    }
+
+    /// Change the charge voltage limit, leaving the other fields untouched.
+    ///
+    /// The limit is clamped to 3504mV - 4400mV and rounded down to the nearest 16mV step.
+    pub fn set_charge_voltage_millivolts(&mut self, millivolts: u16) {
+        let offset = (millivolts.clamp(VREG_MIN_MILLIVOLTS, VREG_MAX_MILLIVOLTS)
+            - VREG_MIN_MILLIVOLTS)
+            / VREG_STEP_MILLIVOLTS;
+        *self = Self::from((self.bits() & 0b0000_0011) | ((offset as u8) << 2));
+    }
+
+    /// Battery pre-charge to fast charge threshold
+    pub fn battery_low_voltage(&self) -> BatteryLowVoltage {
+        if self.contains(Self::BATLOWV) {
+            BatteryLowVoltage::Millivolts3000
+        } else {
+            BatteryLowVoltage::Millivolts2800
+        }
+    }
+
+    /// Change the battery pre-charge to fast charge threshold, leaving the other fields untouched
+    pub fn set_battery_low_voltage(&mut self, threshold: BatteryLowVoltage) {
+        *self &= !Self::BATLOWV;
+        if threshold == BatteryLowVoltage::Millivolts3000 {
+            *self |= Self::BATLOWV;
+        }
+    }
+
+    /// Battery recharge threshold
+    pub fn recharge_threshold(&self) -> RechargeThreshold {
+        if self.contains(Self::VRECHG) {
+            RechargeThreshold::Millivolts300
+        } else {
+            RechargeThreshold::Millivolts100
+        }
+    }
+
+    /// Change the battery recharge threshold, leaving the other fields untouched
+    pub fn set_recharge_threshold(&mut self, threshold: RechargeThreshold) {
+        *self &= !Self::VRECHG;
+        if threshold == RechargeThreshold::Millivolts300 {
+            *self |= Self::VRECHG;
+        }
+    }
+}
+
+/// Lowest minimum system voltage limit in mV, when `SYS_MIN[2:0]` = 000
+pub const SYS_MIN_MIN_MILLIVOLTS: u16 = 3000;
+/// Highest minimum system voltage limit in mV, when `SYS_MIN[2:0]` = 111
+pub const SYS_MIN_MAX_MILLIVOLTS: u16 = 3700;
+/// Minimum system voltage limit step size in mV
+pub const SYS_MIN_STEP_MILLIVOLTS: u16 = 100;
+
+impl PowerOnConfiguration {
+    /// Minimum system voltage limit in mV
+    pub const fn system_minimum_millivolts(&self) -> u16 {
+        SYS_MIN_MIN_MILLIVOLTS + ((self.bits() >> 1) & 0b111) as u16 * SYS_MIN_STEP_MILLIVOLTS
+    }
+
+    /// Change the minimum system voltage limit, leaving the other fields untouched.
+    ///
+    /// The limit is clamped to 3.0V - 3.7V and rounded down to the nearest 100mV step.
+    pub fn set_system_minimum_millivolts(&mut self, millivolts: u16) {
+        let offset = (millivolts.clamp(SYS_MIN_MIN_MILLIVOLTS, SYS_MIN_MAX_MILLIVOLTS)
+            - SYS_MIN_MIN_MILLIVOLTS)
+            / SYS_MIN_STEP_MILLIVOLTS;
+        *self = Self::from((self.bits() & !0b0000_1110) | ((offset as u8) << 1));
+    }
+}
+
+/// Thermal regulation threshold, set in [`ThermalRegulationControl::TREG[1:0]`](../struct.ThermalRegulationControl.html#associatedconstant.TREG_1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThermalRegulationThreshold {
+    /// 60°C (00)
+    Celsius60,
+    /// 80°C (01)
+    Celsius80,
+    /// 100°C (10)
+    Celsius100,
+    /// 120°C (11)
+    Celsius120,
+}
+
+impl ThermalRegulationThreshold {
+    /// Threshold in °C
+    pub const fn celsius(self) -> u8 {
+        match self {
+            ThermalRegulationThreshold::Celsius60 => 60,
+            ThermalRegulationThreshold::Celsius80 => 80,
+            ThermalRegulationThreshold::Celsius100 => 100,
+            ThermalRegulationThreshold::Celsius120 => 120,
+        }
+    }
+
+    /// Value of the `TREG[1:0]` field
+    pub const fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+impl ThermalRegulationControl {
+    /// Thermal regulation threshold
+    pub fn thermal_regulation_threshold(&self) -> ThermalRegulationThreshold {
+        match self.bits() & 0b11 {
+            0b00 => ThermalRegulationThreshold::Celsius60,
+            0b01 => ThermalRegulationThreshold::Celsius80,
+            0b10 => ThermalRegulationThreshold::Celsius100,
+            _ => ThermalRegulationThreshold::Celsius120,
+        }
+    }
+
+    /// Change the thermal regulation threshold, leaving the other fields untouched
+    pub fn set_thermal_regulation_threshold(&mut self, threshold: ThermalRegulationThreshold) {
+        let value = threshold.bits();
+        *self = Self::from((self.bits() & !0b0000_0011) | value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{ScriptedBus, SimulatedCharger};
+    use crate::NUM_REGISTERS;
+
+    /// Bus whose register file starts with `registers` at the given addresses
+    fn bus(registers: &[(u8, u8)]) -> ScriptedBus<'static> {
+        let mut sim = SimulatedCharger::new();
+        for &(address, value) in registers {
+            sim.set_register(address, value);
+        }
+        ScriptedBus::new(sim)
+    }
+
+    #[test]
+    fn interrupt_mask_only_changes_int_mask() {
+        // TMR2X_EN, BATFET_DISABLE, reserved and both faults unmasked
+        let mut i2c = bus(&[(0x07, 0b0110_1011)]);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        let mask = InterruptMask {
+            charge_fault: false,
+            battery_fault: true,
+        };
+        state.write_interrupt_mask(&mut i2c, mask).unwrap();
+        assert_eq!(i2c.writes, [[0x07, 0b0110_1001]]);
+        assert_eq!(state.get_interrupt_mask(), mask);
+    }
+
+    #[test]
+    fn interrupt_mask_clears_dpdm_en() {
+        let mut i2c = bus(&[(0x07, 0b1100_1011)]);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        state
+            .write_interrupt_mask(&mut i2c, InterruptMask::default())
+            .unwrap();
+        assert_eq!(i2c.writes, [[0x07, 0b0100_1011]]);
+    }
+
+    #[test]
+    fn safety_timer_is_disabled_while_changed() {
+        // EN_TERM, 40s watchdog, EN_TIMER, 8 hours
+        let mut i2c = bus(&[(0x05, 0b1001_1010)]);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        state
+            .set_safety_timer(&mut i2c, SafetyTimer::Hours20)
+            .unwrap();
+        assert_eq!(
+            i2c.writes,
+            [
+                [0x05, 0b1001_0010],
+                [0x05, 0b1001_0110],
+                [0x05, 0b1001_1110]
+            ]
+        );
+    }
+
+    #[test]
+    fn disabled_safety_timer_is_changed_in_one_write() {
+        let mut i2c = bus(&[(0x05, 0b1001_0010)]);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        state
+            .set_safety_timer(&mut i2c, SafetyTimer::Hours20)
+            .unwrap();
+        assert_eq!(i2c.writes, [[0x05, 0b1001_0110]]);
+    }
+
+    #[test]
+    fn charger_mode_only_changes_chg_config() {
+        // Register and watchdog reset, charging, SYS_MIN 3.1V, BOOST_LIM 1.3A, reserved bit clear
+        let mut i2c = bus(&[(0x01, 0b1101_0010)]);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        assert_eq!(state.enable_otg(&mut i2c), Ok(ChargerMode::Charge));
+        assert_eq!(state.disable_charging(&mut i2c), Ok(ChargerMode::Otg));
+        assert_eq!(
+            state.enable_charging(&mut i2c),
+            Ok(ChargerMode::ChargeDisabled)
+        );
+        assert_eq!(
+            i2c.writes,
+            [
+                [0x01, 0b0010_0011],
+                [0x01, 0b0000_0011],
+                [0x01, 0b0001_0011]
+            ]
+        );
+    }
+
+    #[test]
+    fn tmr2x_en_doubles_safety_timer_in_dpm() {
+        // 1.5A input limit, 8 hour timer, fast charging in DPM with power good
+        let registers = [(0x00, 0x35), (0x05, 0b1001_1010), (0x08, 0b0010_1100)];
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+
+        let mut i2c = bus(&registers);
+        i2c.sim.set_register(0x07, 0b0100_1011);
+        assert_eq!(state.effective_safety_timer(&mut i2c), Ok(Some(16 * 60)));
+
+        i2c.sim.set_register(0x07, 0b0000_1011);
+        assert_eq!(state.effective_safety_timer(&mut i2c), Ok(Some(8 * 60)));
+
+        i2c.sim.set_register(0x07, 0b0100_1011);
+        i2c.sim.set_register(0x08, 0b0010_0100);
+        assert_eq!(state.effective_safety_timer(&mut i2c), Ok(Some(8 * 60)));
+    }
+}
@@ -0,0 +1,153 @@
+//! Forced USB D+/D- input source detection.
+//!
+//! Writing 1 to [`MiscOperationControl::DPDM_EN`](../struct.MiscOperationControl.html#associatedconstant.DPDM_EN) makes the chip repeat the detection it does when VBUS is plugged in.
+//! The bit returns to 0 once detection is done, at which point [`SystemStatus::VBUS_STAT[1:0]`](../struct.SystemStatus.html#associatedconstant.VBUS_STAT_1)
+//! and [`InputSourceControl::IINLIM[2:0]`](../struct.InputSourceControl.html#associatedconstant.IINLIM_2) hold the result.
+
+use crate::control::InputCurrentLimit;
+use crate::status::VbusStatus;
+use crate::{ChargerState, Error, MiscOperationControl};
+use hal::blocking::delay::DelayMs;
+use hal::blocking::i2c::{Write, WriteRead};
+
+/// How often [`MiscOperationControl::DPDM_EN`](../struct.MiscOperationControl.html#associatedconstant.DPDM_EN) is polled
+const POLL_INTERVAL_MS: u8 = 10;
+
+/// Result of input source detection
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputDetection {
+    /// Detected input source type
+    pub vbus_status: VbusStatus,
+    /// Input current limit the chip picked for the source
+    pub input_current_limit: InputCurrentLimit,
+}
+
+impl ChargerState {
+    /// Force D+/D- detection, wait for it to complete, and return the result.
+    ///
+    /// [`Error::NoInputPower`](enum.Error.html#variant.NoInputPower) is returned and nothing is written if [`SystemStatus::PG_STAT`](struct.SystemStatus.html#associatedconstant.PG_STAT) reports no good input source.
+    /// If [`MiscOperationControl::DPDM_EN`](struct.MiscOperationControl.html#associatedconstant.DPDM_EN) has not cleared within `timeout_ms`,
+    /// the previous value of [`MiscOperationControl`](struct.MiscOperationControl.html) is written back and [`Error::Timeout`](enum.Error.html#variant.Timeout) is returned.
+    pub fn redetect_input_source<E, I2C, D>(
+        &mut self,
+        i2c: &mut I2C,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<InputDetection, Error<E>>
+    where
+        I2C: Write<Error = E> + WriteRead<Error = E>,
+        D: DelayMs<u8>,
+    {
+        self.read_system_status(i2c).map_err(Error::I2c)?;
+        if !self.get_system_status().power_good() {
+            return Err(Error::NoInputPower);
+        }
+        self.read_misc_operation_control(i2c).map_err(Error::I2c)?;
+        let previous = self.get_misc_operation_control() & !MiscOperationControl::DPDM_EN;
+        self.write_misc_operation_control(i2c, previous | MiscOperationControl::DPDM_EN)
+            .map_err(Error::I2c)?;
+
+        let mut waited_ms = 0u16;
+        loop {
+            delay.delay_ms(POLL_INTERVAL_MS);
+            waited_ms = waited_ms.saturating_add(POLL_INTERVAL_MS.into());
+            self.read_misc_operation_control(i2c).map_err(Error::I2c)?;
+            if !self
+                .get_misc_operation_control()
+                .contains(MiscOperationControl::DPDM_EN)
+            {
+                break;
+            }
+            if waited_ms >= timeout_ms {
+                self.write_misc_operation_control(i2c, previous)
+                    .map_err(Error::I2c)?;
+                return Err(Error::Timeout);
+            }
+        }
+
+        self.read_system_status(i2c).map_err(Error::I2c)?;
+        self.read_input_source_control(i2c).map_err(Error::I2c)?;
+        Ok(InputDetection {
+            vbus_status: self.get_system_status().vbus_status(),
+            input_current_limit: self.get_input_source_control().input_current_limit(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{ScriptedBus, SimulatedCharger};
+    use crate::NUM_REGISTERS;
+
+    /// Delay that only adds up how long it was asked to wait
+    #[derive(Default)]
+    struct Elapsed(u32);
+
+    impl DelayMs<u8> for Elapsed {
+        fn delay_ms(&mut self, ms: u8) {
+            self.0 += u32::from(ms);
+        }
+    }
+
+    fn sim(system_status: u8) -> SimulatedCharger {
+        let mut sim = SimulatedCharger::new();
+        sim.set_register(0x08, system_status);
+        sim
+    }
+
+    #[test]
+    fn detection_result_is_read_back() {
+        // The chip finds a charging port and picks 1.5A
+        let mut i2c = ScriptedBus::with_script(sim(0b0000_0100), |sim| {
+            sim.set_register(0x08, 0b1000_0100);
+            sim.set_register(0x00, 0x35);
+        });
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        let mut delay = Elapsed::default();
+        assert_eq!(
+            state.redetect_input_source(&mut i2c, &mut delay, 100),
+            Ok(InputDetection {
+                vbus_status: VbusStatus::AdapterPort,
+                input_current_limit: InputCurrentLimit::Milliamps1500,
+            })
+        );
+        assert_eq!(i2c.writes, [[0x07, 0xCB]]);
+        assert_eq!(delay.0, u32::from(POLL_INTERVAL_MS));
+    }
+
+    #[test]
+    fn detection_needs_input_power() {
+        let mut i2c = ScriptedBus::new(sim(0));
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        assert_eq!(
+            state.redetect_input_source(&mut i2c, &mut Elapsed::default(), 100),
+            Err(Error::NoInputPower)
+        );
+        assert!(i2c.writes.is_empty());
+    }
+
+    #[test]
+    fn timeout_restores_previous_state() {
+        // Detection never finishes
+        let mut i2c = ScriptedBus::with_script(sim(0b0000_0100), |sim| {
+            let misc_operation_control = sim.registers()[0x07];
+            sim.set_register(0x07, misc_operation_control | 0x80);
+        });
+        i2c.sim.set_register(0x07, 0b0110_1011);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        let mut delay = Elapsed::default();
+        assert_eq!(
+            state.redetect_input_source(&mut i2c, &mut delay, 100),
+            Err(Error::Timeout)
+        );
+        assert_eq!(i2c.writes, [[0x07, 0b1110_1011], [0x07, 0b0110_1011]]);
+        assert_eq!(delay.0, 100);
+        assert_eq!(
+            state.get_misc_operation_control(),
+            MiscOperationControl::from(0b0110_1011)
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Parsers for text dumps of the registers, so a chip state can be analyzed without hardware.
+//!
+//! Two formats are accepted:
+//!
+//! * Output of `i2cdump -y 1 0x6b` from i2c-tools, with or without a `-r` range
+//! * Hex dumps of the registers starting from 0x00, i.e. `35 1b 60 ...`, `0x35, 0x1B, ...`, `35:1b:60:...` or `351b60...`
+//!
+//! [`parse`](fn.parse.html) detects the format, and is also used by `str::parse::<ChargerState>()`.
+//! It needs every register (0x00 - 0x0A), so a `-r` range must cover them all. Values past the last register are ignored.
+//! [`parse_partial`](fn.parse_partial.html) accepts dumps with registers that are missing or unreadable (`XX`).
+//!
+//! ```
+//! # #[cfg(feature = "std")]
+//! # fn main() {
+//! use bq24195_i2c::ChargerState;
+//!
+//! let dump = "     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f    0123456789abcdef
+//! 00: 35 1b 60 11 b2 9a 03 4b a4 00 23 XX XX XX XX XX    5?`?????.?#.....";
+//! let state: ChargerState = dump.parse().unwrap();
+//! assert_eq!(
+//!     state.status_summary().to_string(),
+//!     "adapter-port, fast-charging, power good"
+//! );
+//!
+//! let before: ChargerState = "30 1b 60 11 b2 9a 03 4b a4 00 23".parse().unwrap();
+//! let changes: Vec<_> = before.diff(&state).map(|change| change.field.name).collect();
+//! assert_eq!(changes, ["IINLIM"]);
+//! # }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() {}
+//! ```
+
+use crate::{ChargerState, NUM_REGISTERS};
+use core::fmt;
+use core::str::FromStr;
+
+/// Reason a dump could not be parsed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Text that is not a hex byte, at a line and column counting from 1
+    InvalidByte {
+        /// Line of the text
+        line: usize,
+        /// Column of the text
+        column: usize,
+    },
+    /// i2cdump could not read the register (`XX`)
+    Unreadable(u8),
+    /// The dump does not include the register
+    Missing(u8),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidByte { line, column } => {
+                write!(f, "not a hex byte at line {}, column {}", line, column)
+            }
+            ParseError::Unreadable(address) => {
+                write!(f, "register {:#04x} was not readable", address)
+            }
+            ParseError::Missing(address) => write!(f, "register {:#04x} is missing", address),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Content of a register in a dump
+#[derive(Copy, Clone)]
+enum Cell {
+    /// The dump does not include the register
+    Missing,
+    /// i2cdump could not read the register (`XX`)
+    Unreadable,
+    Value(u8),
+}
+
+/// Parse `i2cdump` output if any line looks like the `i2cdump` header or one of its rows, and a hex dump otherwise. Every register must be included and readable.
+pub fn parse(dump: &str) -> Result<ChargerState, ParseError> {
+    collect(cells(dump)?)
+}
+
+/// Parse a dump that may not include every register, i.e. `i2cdump -r 0x08-0x09`, or have some that were not readable.
+/// Registers that are missing or unreadable are `None`.
+///
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use bq24195_i2c::dump::parse_partial;
+///
+/// let dump = "     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f    0123456789abcdef
+/// 00:                         a4 XX                            ?X";
+/// let registers = parse_partial(dump).unwrap();
+/// assert_eq!(registers[0x08], Some(0xa4));
+/// assert_eq!(registers[0x09], None);
+/// assert_eq!(registers[0x0A], None);
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+pub fn parse_partial(dump: &str) -> Result<[Option<u8>; NUM_REGISTERS], ParseError> {
+    let mut values = [None; NUM_REGISTERS];
+    for (value, cell) in values.iter_mut().zip(cells(dump)?.iter()) {
+        if let Cell::Value(bits) = cell {
+            *value = Some(*bits);
+        }
+    }
+    Ok(values)
+}
+
+fn cells(dump: &str) -> Result<[Cell; NUM_REGISTERS], ParseError> {
+    if dump
+        .lines()
+        .any(|line| i2cdump_header(line) || i2cdump_row(line).is_some())
+    {
+        i2cdump_cells(dump)
+    } else {
+        hex_cells(dump)
+    }
+}
+
+/// Parse the output of `i2cdump`. Lines that are not rows of the dump, like the header and warnings, are skipped.
+/// Every register must be included and readable, see [`parse_partial`](fn.parse_partial.html) otherwise.
+pub fn parse_i2cdump(dump: &str) -> Result<ChargerState, ParseError> {
+    collect(i2cdump_cells(dump)?)
+}
+
+fn i2cdump_cells(dump: &str) -> Result<[Cell; NUM_REGISTERS], ParseError> {
+    let mut cells = [Cell::Missing; NUM_REGISTERS];
+    for (index, line) in dump.lines().enumerate() {
+        let (row, text) = match i2cdump_row(line) {
+            Some(row) => row,
+            None => continue,
+        };
+        // Each cell is ` xx` after the `00:` row address, and blank if it is outside of the `-r` range
+        for cell in 0..16 {
+            let address = row + cell;
+            if address >= NUM_REGISTERS {
+                break;
+            }
+            let start = 1 + cell * 3;
+            let value = match text.get(start..start + 2) {
+                Some(value) => value.trim(),
+                None => break,
+            };
+            cells[address] = match value {
+                "" => continue,
+                "XX" => Cell::Unreadable,
+                _ => Cell::Value(u8::from_str_radix(value, 16).map_err(|_| {
+                    ParseError::InvalidByte {
+                        line: index + 1,
+                        column: line.len() - text.len() + start + 1,
+                    }
+                })?),
+            };
+        }
+    }
+    Ok(cells)
+}
+
+/// Parse a hex dump of the registers starting from 0x00. Bytes can be separated by whitespace, commas or colons, and prefixed with `0x`.
+/// Lines starting with `#` are skipped.
+pub fn parse_hex(dump: &str) -> Result<ChargerState, ParseError> {
+    collect(hex_cells(dump)?)
+}
+
+fn hex_cells(dump: &str) -> Result<[Cell; NUM_REGISTERS], ParseError> {
+    let mut cells = [Cell::Missing; NUM_REGISTERS];
+    let mut address = 0;
+    for (index, line) in dump.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        let mut column = 0;
+        // Separators are all one byte long, so the column advances by the length of each token plus one
+        for token in line.split(|c: char| c.is_whitespace() || c == ',' || c == ':') {
+            let start = column;
+            column += token.len() + 1;
+            let digits = token
+                .strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+                .unwrap_or(token);
+            if digits.is_empty() {
+                continue;
+            }
+            let error = ParseError::InvalidByte {
+                line: index + 1,
+                column: start + 1,
+            };
+            if digits.len() % 2 != 0 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(error);
+            }
+            for pair in (0..digits.len()).step_by(2) {
+                let value = u8::from_str_radix(&digits[pair..pair + 2], 16).map_err(|_| error)?;
+                if address < NUM_REGISTERS {
+                    cells[address] = Cell::Value(value);
+                }
+                address += 1;
+            }
+        }
+    }
+    Ok(cells)
+}
+
+/// True for the `0  1  2 ...  f` header line of `i2cdump`
+fn i2cdump_header(line: &str) -> bool {
+    let mut columns = line.split_whitespace();
+    (0..16).all(|column| {
+        columns
+            .next()
+            .and_then(|text| usize::from_str_radix(text, 16).ok())
+            == Some(column)
+    })
+}
+
+/// Address of the first cell in an `i2cdump` row and the text after its `xx:` prefix, i.e. 0x10 for `10: 00 ...`
+///
+/// The cells after the prefix are separated by spaces, so a colon-separated hex dump like `30:1b:60` is not a row.
+fn i2cdump_row(line: &str) -> Option<(usize, &str)> {
+    let (address, cells) = line.split_once(':')?;
+    if address.len() != 2
+        || !address.bytes().all(|b| b.is_ascii_hexdigit())
+        || !cells.starts_with(' ')
+    {
+        return None;
+    }
+    let row = usize::from_str_radix(address, 16).ok()?;
+    if row % 16 != 0 {
+        return None;
+    }
+    Some((row, cells))
+}
+
+fn collect(cells: [Cell; NUM_REGISTERS]) -> Result<ChargerState, ParseError> {
+    let mut registers = [0u8; NUM_REGISTERS];
+    for (address, cell) in cells.iter().enumerate() {
+        registers[address] = match cell {
+            Cell::Value(value) => *value,
+            Cell::Unreadable => return Err(ParseError::Unreadable(address as u8)),
+            Cell::Missing => return Err(ParseError::Missing(address as u8)),
+        };
+    }
+    Ok(ChargerState::from_registers(registers))
+}
+
+/// Same as [`parse`](dump/fn.parse.html)
+impl FromStr for ChargerState {
+    type Err = ParseError;
+
+    fn from_str(dump: &str) -> Result<Self, Self::Err> {
+        parse(dump)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f    0123456789abcdef";
+    const REGISTERS: [u8; NUM_REGISTERS] = [
+        0x35, 0x1b, 0x60, 0x11, 0xb2, 0x9a, 0x03, 0x4b, 0xa4, 0x00, 0x23,
+    ];
+
+    fn i2cdump(row: &str) -> String {
+        format!(
+            "No size specified (using byte-data access)\n{}\n{}\n10: XX XX XX XX XX XX XX XX XX XX XX XX XX XX XX XX    XXXXXXXXXXXXXXXX",
+            HEADER, row
+        )
+    }
+
+    #[test]
+    fn full_i2cdump() {
+        let dump =
+            i2cdump("00: 35 1b 60 11 b2 9a 03 4b a4 00 23 XX XX XX XX XX    5?`?????.?#.....");
+        assert_eq!(parse(&dump).unwrap().registers(), REGISTERS);
+    }
+
+    #[test]
+    fn range_i2cdump() {
+        let dump = i2cdump("00:                         a4 XX                            ?X");
+        assert_eq!(parse(&dump), Err(ParseError::Missing(0x00)));
+        let mut expected = [None; NUM_REGISTERS];
+        expected[0x08] = Some(0xa4);
+        assert_eq!(parse_partial(&dump), Ok(expected));
+    }
+
+    #[test]
+    fn unreadable_register() {
+        let dump =
+            i2cdump("00: 35 1b 60 11 b2 9a 03 4b a4 XX 23 XX XX XX XX XX    5?`?????.?X#.....");
+        assert_eq!(parse(&dump), Err(ParseError::Unreadable(0x09)));
+        let registers = parse_partial(&dump).unwrap();
+        assert_eq!(registers[0x08], Some(0xa4));
+        assert_eq!(registers[0x09], None);
+        assert_eq!(registers[0x0A], Some(0x23));
+    }
+
+    #[test]
+    fn invalid_i2cdump_byte() {
+        let dump = "00: 35 1b 6g 11 b2 9a 03 4b a4 00 23";
+        assert_eq!(
+            parse(dump),
+            Err(ParseError::InvalidByte {
+                line: 1,
+                column: 11
+            })
+        );
+    }
+
+    #[test]
+    fn lines_that_are_not_rows() {
+        for line in [
+            "Error: Could not open file",
+            "ab: 35 1b",
+            "0x: 35",
+            "1: 35",
+            "08: 35",
+            "30:1b:60:11",
+        ] {
+            assert!(i2cdump_row(line).is_none(), "{}", line);
+        }
+        assert_eq!(i2cdump_row("10: 00"), Some((0x10, " 00")));
+    }
+
+    #[test]
+    fn hex_dumps() {
+        for dump in [
+            "35 1b 60 11 b2 9a 03 4b a4 00 23",
+            "0x35, 0x1B, 0x60, 0x11, 0xB2, 0x9A, 0x03, 0x4B, 0xA4, 0x00, 0x23",
+            "# registers\n351b6011b29a034b\na40023ffff",
+            "35:1b:60:11:b2:9a:03:4b:a4:00:23",
+        ] {
+            assert_eq!(parse(dump).unwrap().registers(), REGISTERS, "{}", dump);
+        }
+        assert_eq!(parse("35 1b"), Err(ParseError::Missing(0x02)));
+        assert_eq!(
+            parse("35 1b 6"),
+            Err(ParseError::InvalidByte { line: 1, column: 7 })
+        );
+    }
+
+    #[test]
+    fn colon_separated_hex_dump_starting_at_row_address() {
+        // 0x30 is also the address of an i2cdump row
+        assert_eq!(
+            parse("30:1b:60:11:b2:9a:03:4b:a4:00:23")
+                .unwrap()
+                .registers(),
+            [0x30, 0x1b, 0x60, 0x11, 0xb2, 0x9a, 0x03, 0x4b, 0xa4, 0x00, 0x23]
+        );
+    }
+
+    #[test]
+    fn header_selects_i2cdump() {
+        assert!(i2cdump_header(HEADER));
+        assert!(!i2cdump_header("35 1b 60 11 b2 9a 03 4b a4 00 23"));
+        // Without a row, nothing is included
+        assert_eq!(parse(HEADER), Err(ParseError::Missing(0x00)));
+    }
+}
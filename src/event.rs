@@ -0,0 +1,443 @@
+//! Change events decoded from the [`SystemStatus`](../struct.SystemStatus.html) and [`Fault`](../struct.Fault.html) registers.
+//!
+//! BQ24195 pulses the INT pin low for 256us whenever a status bit changes or a fault occurs.
+//! [`EventMonitor`](struct.EventMonitor.html) compares successive readings of both registers and yields one [`Event`](enum.Event.html) per changed field,
+//! so it can be driven by any polling loop.
+//!
+//! With the `async` feature, [`EventStream`](struct.EventStream.html) wraps an async I2C bus and a [`Wake`](trait.Wake.html) source (either the INT pin or a poll timer) and provides `next_event().await`.
+//!
+//! Faults can be kept from pulsing INT with an [`InterruptMask`](../control/struct.InterruptMask.html). Masked faults are still reported,
+//! but only once some other change wakes the stream.
+//!
+//! Note that [`Fault`](../struct.Fault.html) is latched: the first read after a fault reports it, and the next read reports the current state.
+//! A fault that occurred and cleared between two wakeups will therefore still be reported, followed by a change back to normal.
+
+use crate::status::{ChargeFault, ChargeStatus, NtcFault, VbusStatus};
+use crate::{Fault, SystemStatus};
+
+/// A change in a single field of [`SystemStatus`](../struct.SystemStatus.html) or [`Fault`](../struct.Fault.html), carrying the new value
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Event {
+    /// [`Fault::WATCHDOG_FAULT`](../struct.Fault.html#associatedconstant.WATCHDOG_FAULT) changed
+    WatchdogFaultChanged(bool),
+    /// [`Fault::BOOST_FAULT`](../struct.Fault.html#associatedconstant.BOOST_FAULT) changed
+    BoostFaultChanged(bool),
+    /// [`Fault::CHRG_FAULT[1:0]`](../struct.Fault.html#associatedconstant.CHRG_FAULT_1) changed
+    ChargeFaultChanged(ChargeFault),
+    /// [`Fault::BAT_FAULT`](../struct.Fault.html#associatedconstant.BAT_FAULT) changed
+    BatteryFaultChanged(bool),
+    /// [`Fault::NTC_FAULT[2:0]`](../struct.Fault.html#associatedconstant.NTC_FAULT_2) changed
+    NtcFaultChanged(NtcFault),
+    /// [`SystemStatus::VBUS_STAT[1:0]`](../struct.SystemStatus.html#associatedconstant.VBUS_STAT_1) changed
+    VbusStatusChanged(VbusStatus),
+    /// [`SystemStatus::CHRG_STAT[1:0]`](../struct.SystemStatus.html#associatedconstant.CHRG_STAT_1) changed
+    ChargeStatusChanged(ChargeStatus),
+    /// [`SystemStatus::DPM_STAT`](../struct.SystemStatus.html#associatedconstant.DPM_STAT) changed
+    DpmChanged(bool),
+    /// [`SystemStatus::PG_STAT`](../struct.SystemStatus.html#associatedconstant.PG_STAT) changed
+    PowerGoodChanged(bool),
+    /// [`SystemStatus::THERM_STAT`](../struct.SystemStatus.html#associatedconstant.THERM_STAT) changed
+    ThermalRegulationChanged(bool),
+    /// [`SystemStatus::VSYS_STAT`](../struct.SystemStatus.html#associatedconstant.VSYS_STAT) changed
+    VsysRegulationChanged(bool),
+}
+
+/// Tracks the last reported [`SystemStatus`](../struct.SystemStatus.html) and [`Fault`](../struct.Fault.html) and turns new readings into [`Event`](enum.Event.html)s.
+///
+/// For blocking code, read the registers with [`ChargerState::read_status`](../struct.ChargerState.html#method.read_status),
+/// pass them to [`update`](#method.update), then drain [`next_event`](#method.next_event) until it returns `None`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EventMonitor {
+    status: SystemStatus,
+    fault: Fault,
+    reported_status: SystemStatus,
+    reported_fault: Fault,
+}
+
+impl EventMonitor {
+    /// Create a monitor whose baseline is the given readings. No events are generated for the baseline itself.
+    pub fn new(status: SystemStatus, fault: Fault) -> Self {
+        Self {
+            status,
+            fault,
+            reported_status: status,
+            reported_fault: fault,
+        }
+    }
+
+    /// Record a new reading of both registers
+    pub fn update(&mut self, status: SystemStatus, fault: Fault) {
+        self.status = status;
+        self.fault = fault;
+    }
+
+    /// Most recent [`SystemStatus`](../struct.SystemStatus.html) passed to [`update`](#method.update)
+    pub fn status(&self) -> SystemStatus {
+        self.status
+    }
+
+    /// Most recent [`Fault`](../struct.Fault.html) passed to [`update`](#method.update)
+    pub fn fault(&self) -> Fault {
+        self.fault
+    }
+
+    /// Get the next field that changed since it was last reported, or `None` if every change has been reported.
+    ///
+    /// Faults are reported before status changes.
+    pub fn next_event(&mut self) -> Option<Event> {
+        let fault = self.fault;
+        let status = self.status;
+        if self.take_fault(Fault::WATCHDOG_FAULT) {
+            Some(Event::WatchdogFaultChanged(fault.watchdog_fault()))
+        } else if self.take_fault(Fault::BOOST_FAULT) {
+            Some(Event::BoostFaultChanged(fault.boost_fault()))
+        } else if self.take_fault(Fault::CHRG_FAULT_1 | Fault::CHRG_FAULT_0) {
+            Some(Event::ChargeFaultChanged(fault.charge_fault()))
+        } else if self.take_fault(Fault::BAT_FAULT) {
+            Some(Event::BatteryFaultChanged(fault.battery_fault()))
+        } else if self.take_fault(Fault::NTC_FAULT_2 | Fault::NTC_FAULT_1 | Fault::NTC_FAULT_0) {
+            Some(Event::NtcFaultChanged(fault.ntc_fault()))
+        } else if self.take_status(SystemStatus::VBUS_STAT_1 | SystemStatus::VBUS_STAT_0) {
+            Some(Event::VbusStatusChanged(status.vbus_status()))
+        } else if self.take_status(SystemStatus::CHRG_STAT_1 | SystemStatus::CHRG_STAT_0) {
+            Some(Event::ChargeStatusChanged(status.charge_status()))
+        } else if self.take_status(SystemStatus::DPM_STAT) {
+            Some(Event::DpmChanged(status.dpm()))
+        } else if self.take_status(SystemStatus::PG_STAT) {
+            Some(Event::PowerGoodChanged(status.power_good()))
+        } else if self.take_status(SystemStatus::THERM_STAT) {
+            Some(Event::ThermalRegulationChanged(status.thermal_regulation()))
+        } else if self.take_status(SystemStatus::VSYS_STAT) {
+            Some(Event::VsysRegulationChanged(status.vsys_regulation()))
+        } else {
+            None
+        }
+    }
+
+    /// Mark the bits of `mask` as reported, returning true if any of them changed
+    fn take_fault(&mut self, mask: Fault) -> bool {
+        let changed = (self.fault ^ self.reported_fault) & mask;
+        self.reported_fault ^= changed;
+        changed.bits() != 0
+    }
+
+    /// Mark the bits of `mask` as reported, returning true if any of them changed
+    fn take_status(&mut self, mask: SystemStatus) -> bool {
+        let changed = (self.status ^ self.reported_status) & mask;
+        self.reported_status ^= changed;
+        changed.bits() != 0
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::stream::*;
+
+#[cfg(feature = "async")]
+mod stream {
+    use super::{Event, EventMonitor};
+    use crate::{Fault, InterruptMask, MiscOperationControl, SystemStatus, ADDRESS};
+    use core::convert::Infallible;
+    use embedded_hal_async::{delay::DelayNs, digital::Wait, i2c::I2c};
+
+    /// Something that completes when the charger status may have changed
+    pub trait Wake {
+        /// Error produced while waiting
+        type Error;
+
+        /// Wait until the registers should be read again
+        #[allow(async_fn_in_trait)]
+        async fn wait(&mut self) -> Result<(), Self::Error>;
+    }
+
+    /// Wake on the falling edge of the active-low INT pin
+    pub struct IntPin<P>(pub P);
+
+    impl<P: Wait> Wake for IntPin<P> {
+        type Error = P::Error;
+
+        async fn wait(&mut self) -> Result<(), Self::Error> {
+            self.0.wait_for_falling_edge().await
+        }
+    }
+
+    /// Wake at a fixed interval, for boards where INT is not connected
+    pub struct PollTimer<D> {
+        delay: D,
+        interval_ms: u32,
+    }
+
+    impl<D: DelayNs> PollTimer<D> {
+        /// Poll every `interval_ms` milliseconds
+        pub fn new(delay: D, interval_ms: u32) -> Self {
+            Self { delay, interval_ms }
+        }
+    }
+
+    impl<D: DelayNs> Wake for PollTimer<D> {
+        type Error = Infallible;
+
+        async fn wait(&mut self) -> Result<(), Self::Error> {
+            self.delay.delay_ms(self.interval_ms).await;
+            Ok(())
+        }
+    }
+
+    /// Error from an [`EventStream`](struct.EventStream.html)
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum StreamError<I, W> {
+        /// Reading the registers failed
+        I2c(I),
+        /// Waiting on the wake source failed
+        Wake(W),
+    }
+
+    /// Asynchronous stream of charger [`Event`](enum.Event.html)s
+    pub struct EventStream<I2C, W> {
+        i2c: I2C,
+        wake: W,
+        monitor: EventMonitor,
+    }
+
+    impl<I2C: I2c, W: Wake> EventStream<I2C, W> {
+        /// Read the current status and fault registers as a baseline and start listening for changes
+        pub async fn new(mut i2c: I2C, wake: W) -> Result<Self, I2C::Error> {
+            let (status, fault) = read_status(&mut i2c).await?;
+            Ok(Self {
+                i2c,
+                wake,
+                monitor: EventMonitor::new(status, fault),
+            })
+        }
+
+        /// Wait for the next change in charger status or faults
+        pub async fn next_event(&mut self) -> Result<Event, StreamError<I2C::Error, W::Error>> {
+            loop {
+                if let Some(event) = self.monitor.next_event() {
+                    return Ok(event);
+                }
+                self.wake.wait().await.map_err(StreamError::Wake)?;
+                let (status, fault) = read_status(&mut self.i2c).await.map_err(StreamError::I2c)?;
+                self.monitor.update(status, fault);
+            }
+        }
+
+        /// Change which faults pulse the INT pin, leaving the rest of [`MiscOperationControl`](../struct.MiscOperationControl.html) untouched
+        pub async fn set_interrupt_mask(&mut self, mask: InterruptMask) -> Result<(), I2C::Error> {
+            let mut value = [0u8; 1];
+            self.i2c.write_read(ADDRESS, &[0x07], &mut value).await?;
+            let mut misc_operation_control =
+                MiscOperationControl::from(value[0]) & !MiscOperationControl::DPDM_EN;
+            misc_operation_control.set_interrupt_mask(mask);
+            self.i2c
+                .write(ADDRESS, &[0x07, misc_operation_control.into()])
+                .await
+        }
+
+        /// The underlying monitor, holding the most recent register readings
+        pub fn monitor(&self) -> &EventMonitor {
+            &self.monitor
+        }
+
+        /// Release the I2C bus and wake source
+        pub fn release(self) -> (I2C, W) {
+            (self.i2c, self.wake)
+        }
+    }
+
+    /// Burst read [`SystemStatus`](../struct.SystemStatus.html) and [`Fault`](../struct.Fault.html)
+    async fn read_status<I2C: I2c>(i2c: &mut I2C) -> Result<(SystemStatus, Fault), I2C::Error> {
+        let mut values = [0u8; 2];
+        i2c.write_read(ADDRESS, &[0x08], &mut values).await?;
+        Ok((values[0].into(), values[1].into()))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::status::VbusStatus;
+        use core::future::Future;
+        use core::task::{Context, Poll, Waker};
+        use embedded_hal_async::i2c::{ErrorType, Operation};
+        use std::collections::VecDeque;
+        use std::sync::Arc;
+
+        /// Poll a future to completion on the current thread
+        fn block_on<F: Future>(future: F) -> F::Output {
+            struct NoopWake;
+            impl std::task::Wake for NoopWake {
+                fn wake(self: Arc<Self>) {}
+            }
+            let waker = Waker::from(Arc::new(NoopWake));
+            let mut cx = Context::from_waker(&waker);
+            let mut future = Box::pin(future);
+            loop {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            }
+        }
+
+        /// Bus that answers each read of 0x08 - 0x09 with the next (status, fault) pair
+        struct Readings(VecDeque<[u8; 2]>);
+
+        impl ErrorType for Readings {
+            type Error = Infallible;
+        }
+
+        impl I2c for Readings {
+            async fn transaction(
+                &mut self,
+                address: u8,
+                operations: &mut [Operation<'_>],
+            ) -> Result<(), Infallible> {
+                assert_eq!(address, ADDRESS);
+                match operations {
+                    [Operation::Write(&[0x08]), Operation::Read(buffer)] => {
+                        buffer.copy_from_slice(&self.0.pop_front().expect("no more readings"));
+                    }
+                    _ => panic!("unexpected transaction"),
+                }
+                Ok(())
+            }
+        }
+
+        /// INT pin that sees a falling edge every time it is waited on
+        #[derive(Default)]
+        struct Edges(usize);
+
+        impl embedded_hal_1::digital::ErrorType for Edges {
+            type Error = Infallible;
+        }
+
+        impl Wait for Edges {
+            async fn wait_for_high(&mut self) -> Result<(), Infallible> {
+                unimplemented!()
+            }
+
+            async fn wait_for_low(&mut self) -> Result<(), Infallible> {
+                unimplemented!()
+            }
+
+            async fn wait_for_rising_edge(&mut self) -> Result<(), Infallible> {
+                unimplemented!()
+            }
+
+            async fn wait_for_falling_edge(&mut self) -> Result<(), Infallible> {
+                self.0 += 1;
+                Ok(())
+            }
+
+            async fn wait_for_any_edge(&mut self) -> Result<(), Infallible> {
+                unimplemented!()
+            }
+        }
+
+        /// Delay that only adds up how long it was asked to wait
+        #[derive(Default)]
+        struct Elapsed(u64);
+
+        impl DelayNs for Elapsed {
+            async fn delay_ns(&mut self, ns: u32) {
+                self.0 += u64::from(ns);
+            }
+        }
+
+        #[test]
+        fn int_edge_reads_new_events() {
+            // Baseline, then adapter port and power good
+            let i2c = Readings([[0, 0], [0b1000_0100, 0]].into());
+            let mut stream = block_on(EventStream::new(i2c, IntPin(Edges::default()))).unwrap();
+            assert_eq!(
+                block_on(stream.next_event()),
+                Ok(Event::VbusStatusChanged(VbusStatus::AdapterPort))
+            );
+            // Already read, so no need to wait again
+            assert_eq!(
+                block_on(stream.next_event()),
+                Ok(Event::PowerGoodChanged(true))
+            );
+            let (i2c, IntPin(edges)) = stream.release();
+            assert!(i2c.0.is_empty());
+            assert_eq!(edges.0, 1);
+        }
+
+        #[test]
+        fn poll_timer_reads_until_something_changes() {
+            // Baseline, unchanged, then a battery fault
+            let i2c = Readings([[0, 0], [0, 0], [0, 0b0000_1000]].into());
+            let timer = PollTimer::new(Elapsed::default(), 500);
+            let mut stream = block_on(EventStream::new(i2c, timer)).unwrap();
+            assert_eq!(
+                block_on(stream.next_event()),
+                Ok(Event::BatteryFaultChanged(true))
+            );
+            let (i2c, timer) = stream.release();
+            assert!(i2c.0.is_empty());
+            assert_eq!(timer.delay.0, 2 * 500 * 1_000_000);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(monitor: &mut EventMonitor) -> Vec<Event> {
+        core::iter::from_fn(|| monitor.next_event()).collect()
+    }
+
+    #[test]
+    fn baseline_has_no_events() {
+        let mut monitor =
+            EventMonitor::new(SystemStatus::from(0b0110_0100), Fault::from(0b0000_0110));
+        assert_eq!(monitor.next_event(), None);
+    }
+
+    #[test]
+    fn one_event_per_changed_field_faults_first() {
+        let mut monitor = EventMonitor::new(SystemStatus::from(0), Fault::from(0));
+        // Adapter port, fast charging, power good, hot
+        monitor.update(SystemStatus::from(0b1010_0100), Fault::from(0b0000_0110));
+        assert_eq!(
+            events(&mut monitor),
+            [
+                Event::NtcFaultChanged(NtcFault::Hot),
+                Event::VbusStatusChanged(VbusStatus::AdapterPort),
+                Event::ChargeStatusChanged(ChargeStatus::FastCharging),
+                Event::PowerGoodChanged(true),
+            ]
+        );
+        assert_eq!(monitor.next_event(), None);
+    }
+
+    #[test]
+    fn multi_bit_field_changes_once() {
+        let mut monitor = EventMonitor::new(SystemStatus::from(0), Fault::from(0b0001_0000));
+        monitor.update(SystemStatus::from(0), Fault::from(0b0011_0000));
+        assert_eq!(
+            events(&mut monitor),
+            [Event::ChargeFaultChanged(ChargeFault::SafetyTimerExpired)]
+        );
+    }
+
+    #[test]
+    fn change_reverted_before_report_is_dropped() {
+        let mut monitor = EventMonitor::new(SystemStatus::from(0), Fault::from(0));
+        monitor.update(SystemStatus::DPM_STAT, Fault::from(0));
+        monitor.update(SystemStatus::from(0), Fault::from(0));
+        assert_eq!(monitor.next_event(), None);
+    }
+
+    #[test]
+    fn latched_fault_reports_clear() {
+        let mut monitor = EventMonitor::new(SystemStatus::from(0), Fault::from(0));
+        monitor.update(SystemStatus::from(0), Fault::BAT_FAULT);
+        assert_eq!(events(&mut monitor), [Event::BatteryFaultChanged(true)]);
+        monitor.update(SystemStatus::from(0), Fault::from(0));
+        assert_eq!(events(&mut monitor), [Event::BatteryFaultChanged(false)]);
+    }
+}
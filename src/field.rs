@@ -0,0 +1,659 @@
+//! Description of every register field, for decoding and comparing chip states.
+//!
+//! [`FIELDS`](constant.FIELDS.html) lists each field by its datasheet name with its register, position and [`Encoding`](enum.Encoding.html),
+//! so a raw register value can be turned into a [`Value`](enum.Value.html) like 1500mA or `fast-charging`.
+//! Reserved bits are not listed; [`ChargerState::validate`](../struct.ChargerState.html#method.validate) checks them instead.
+//!
+//! [`ChargerState::diff`](../struct.ChargerState.html#method.diff) compares two chip states field by field:
+//!
+//! ```
+//! use bq24195_i2c::field::{Unit, Value};
+//! use bq24195_i2c::ChargerState;
+//!
+//! let before = ChargerState::from_registers([0x32, 0x1B, 0x60, 0x11, 0xB2, 0x9A, 0x03, 0x4B, 0, 0, 0x23]);
+//! let after = ChargerState::from_registers([0x35, 0x1B, 0x60, 0x11, 0xB2, 0x9A, 0x03, 0x4B, 0, 0, 0x23]);
+//!
+//! let changes: Vec<_> = before.diff(&after).collect();
+//! assert_eq!(changes.len(), 1);
+//! assert_eq!(changes[0].field.name, "IINLIM");
+//! assert_eq!(changes[0].old, Value::Quantity(500, Unit::Milliamps));
+//! assert_eq!(changes[0].new, Value::Quantity(1500, Unit::Milliamps));
+//! assert_eq!(format!("{} -> {}", changes[0].old, changes[0].new), "500mA -> 1500mA");
+//! ```
+
+use crate::control::{
+    ICHG_MIN_MILLIAMPS, ICHG_STEP_MILLIAMPS, IPRECHG_ITERM_MIN_MILLIAMPS,
+    IPRECHG_ITERM_STEP_MILLIAMPS, SYS_MIN_MIN_MILLIVOLTS, SYS_MIN_STEP_MILLIVOLTS,
+    VINDPM_MIN_MILLIVOLTS, VINDPM_STEP_MILLIVOLTS, VREG_MIN_MILLIVOLTS, VREG_STEP_MILLIVOLTS,
+};
+use crate::ChargerState;
+use core::convert::TryFrom;
+use core::fmt;
+
+/// Unit of a [`Value::Quantity`](enum.Value.html#variant.Quantity)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Unit {
+    /// mV
+    Millivolts,
+    /// mA
+    Milliamps,
+    /// h
+    Hours,
+    /// °C
+    Celsius,
+}
+
+/// How the raw bits of a field map to a [`Value`](enum.Value.html)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Encoding {
+    /// A single bit
+    Flag,
+    /// `offset + raw * step`
+    Linear {
+        /// Value when the raw bits are 0
+        offset: u16,
+        /// Value of the least significant bit
+        step: u16,
+        /// Unit of the value
+        unit: Unit,
+    },
+    /// `values[raw]`
+    Table {
+        /// Value for each setting of the raw bits
+        values: &'static [u16],
+        /// Unit of the values
+        unit: Unit,
+    },
+    /// `names[raw]`. Every name is unique, so settings the datasheet gives the same meaning are told apart by their raw bits, i.e. `otg-11`.
+    Named(&'static [&'static str]),
+    /// Raw bits with no further meaning
+    Raw,
+}
+
+/// A decoded field value
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    /// A single bit
+    Flag(bool),
+    /// A physical quantity
+    Quantity(u16, Unit),
+    /// A named state, i.e. `fast-charging`
+    Named(&'static str),
+    /// Raw bits
+    Raw(u8),
+}
+
+impl Unit {
+    /// Unit symbol, i.e. `mV`
+    pub const fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Millivolts => "mV",
+            Unit::Milliamps => "mA",
+            Unit::Hours => "h",
+            Unit::Celsius => "C",
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// Flags are shown as 0 or 1 and voltages of 1V or more in V, i.e. `4.36V`
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Value::Flag(flag) => write!(f, "{}", u8::from(flag)),
+            Value::Quantity(millivolts, Unit::Millivolts) if millivolts >= 1000 => {
+                write!(f, "{}", millivolts / 1000)?;
+                let mut fraction = millivolts % 1000;
+                if fraction != 0 {
+                    f.write_str(".")?;
+                    while fraction != 0 {
+                        write!(f, "{}", fraction / 100)?;
+                        fraction = fraction % 100 * 10;
+                    }
+                }
+                f.write_str("V")
+            }
+            Value::Quantity(value, unit) => write!(f, "{}{}", value, unit),
+            Value::Named(name) => f.write_str(name),
+            Value::Raw(raw) => write!(f, "{:#b}", raw),
+        }
+    }
+}
+
+/// Same as `Display`, except voltages are always in mV
+#[cfg(feature = "defmt")]
+impl defmt::Format for Value {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match *self {
+            Value::Flag(flag) => defmt::write!(f, "{=u8}", u8::from(flag)),
+            Value::Quantity(value, unit) => defmt::write!(f, "{=u16}{=str}", value, unit.symbol()),
+            Value::Named(name) => defmt::write!(f, "{=str}", name),
+            Value::Raw(raw) => defmt::write!(f, "{=u8:#b}", raw),
+        }
+    }
+}
+
+/// A field of a register
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Field {
+    /// Datasheet name, i.e. `IINLIM`
+    pub name: &'static str,
+    /// Register address
+    pub register: u8,
+    /// Position of the least significant bit
+    pub shift: u8,
+    /// Number of bits
+    pub width: u8,
+    /// How the raw bits are decoded
+    pub encoding: Encoding,
+}
+
+impl Field {
+    /// Bits of the register that hold this field
+    pub const fn mask(&self) -> u8 {
+        (((1u16 << self.width) - 1) << self.shift) as u8
+    }
+
+    /// Raw bits of this field in a register value
+    pub const fn raw(&self, register: u8) -> u8 {
+        (register & self.mask()) >> self.shift
+    }
+
+    /// Decode this field from a register value
+    pub fn decode(&self, register: u8) -> Value {
+        let raw = self.raw(register);
+        match self.encoding {
+            Encoding::Flag => Value::Flag(raw != 0),
+            Encoding::Linear { offset, step, unit } => {
+                Value::Quantity(offset + u16::from(raw) * step, unit)
+            }
+            Encoding::Table { values, unit } => Value::Quantity(values[usize::from(raw)], unit),
+            Encoding::Named(names) => Value::Named(names[usize::from(raw)]),
+            Encoding::Raw => Value::Raw(raw),
+        }
+    }
+
+    /// Raw bits that decode to `value`, or `None` if this field cannot represent it. Names are compared ignoring case.
+    pub fn encode(&self, value: Value) -> Option<u8> {
+        let raw = match (self.encoding, value) {
+            (Encoding::Flag, Value::Flag(flag)) => u16::from(flag),
+            (Encoding::Linear { offset, step, unit }, Value::Quantity(value, value_unit))
+                if unit == value_unit && value >= offset && (value - offset) % step == 0 =>
+            {
+                (value - offset) / step
+            }
+            (Encoding::Table { values, unit }, Value::Quantity(value, value_unit))
+                if unit == value_unit =>
+            {
+                values.iter().position(|v| *v == value)? as u16
+            }
+            (Encoding::Named(names), Value::Named(name)) => {
+                names.iter().position(|n| n.eq_ignore_ascii_case(name))? as u16
+            }
+            (Encoding::Raw, Value::Raw(raw)) => u16::from(raw),
+            _ => return None,
+        };
+        if raw < 1 << self.width {
+            Some(raw as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Parse a value for this field, i.e. `4.2V`, `4200mV`, `1.5A`, `8h`, `120C`, `charge`, `1`, `true` or `0b101`.
+    /// Quantities without a unit are taken in mV, mA, h or °C, and everything `Display` shows for a [`Value`](enum.Value.html) parses back.
+    ///
+    /// Returns `None` if the text is not a value of this field's kind. The value may still not be a setting of this field, see [`encode`](#method.encode).
+    ///
+    /// ```
+    /// use bq24195_i2c::field::{Field, Unit, Value};
+    ///
+    /// let vreg = Field::by_name("vreg").unwrap();
+    /// let value = vreg.parse("4.208V").unwrap();
+    /// assert_eq!(value, Value::Quantity(4208, Unit::Millivolts));
+    /// assert_eq!(vreg.encode(value), Some(44));
+    /// assert_eq!(vreg.encode(vreg.parse("4.2V").unwrap()), None);
+    /// ```
+    pub fn parse(&self, text: &str) -> Option<Value> {
+        let text = text.trim();
+        match self.encoding {
+            Encoding::Flag => ["0", "false", "off"]
+                .iter()
+                .chain(&["1", "true", "on"])
+                .position(|word| word.eq_ignore_ascii_case(text))
+                .map(|index| Value::Flag(index >= 3)),
+            Encoding::Linear { unit, .. } | Encoding::Table { unit, .. } => {
+                parse_quantity(text, unit).map(|value| Value::Quantity(value, unit))
+            }
+            Encoding::Named(names) => names
+                .iter()
+                .find(|name| name.eq_ignore_ascii_case(text))
+                .map(|name| Value::Named(name)),
+            Encoding::Raw => {
+                let raw = if let Some(bits) = strip_prefix_ignore_case(text, "0b") {
+                    u8::from_str_radix(bits, 2)
+                } else if let Some(digits) = strip_prefix_ignore_case(text, "0x") {
+                    u8::from_str_radix(digits, 16)
+                } else {
+                    text.parse()
+                };
+                raw.ok().map(Value::Raw)
+            }
+        }
+    }
+
+    /// Look up a field by its datasheet name, ignoring case
+    pub fn by_name(name: &str) -> Option<&'static Field> {
+        FIELDS
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Parse a decimal quantity with an optional unit symbol into `unit`, i.e. `4.2V` into 4200 for mV
+fn parse_quantity(text: &str, unit: Unit) -> Option<u16> {
+    let units: &[(&str, u32)] = match unit {
+        Unit::Millivolts => &[("mV", 1), ("V", 1000)],
+        Unit::Milliamps => &[("mA", 1), ("A", 1000)],
+        Unit::Hours => &[("h", 1)],
+        Unit::Celsius => &[("°C", 1), ("C", 1)],
+    };
+    let (number, scale) = units
+        .iter()
+        .find_map(|(symbol, scale)| {
+            strip_suffix_ignore_case(text, symbol).map(|number| (number.trim_end(), *scale))
+        })
+        .unwrap_or((text, 1));
+    let (whole, fraction) = match number.find('.') {
+        Some(point) => (&number[..point], &number[point + 1..]),
+        None => (number, ""),
+    };
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let mut value = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<u32>().ok()?.checked_mul(scale)?
+    };
+    let mut place = scale;
+    for digit in fraction.bytes() {
+        place /= 10;
+        let digit = u32::from(digit - b'0');
+        // Digits finer than the unit must be 0, i.e. 4.2005V is not a whole number of mV
+        if place == 0 && digit != 0 {
+            return None;
+        }
+        value = value.checked_add(digit * place)?;
+    }
+    u16::try_from(value).ok()
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let (head, rest) = (text.get(..prefix.len())?, text.get(prefix.len()..)?);
+    if head.eq_ignore_ascii_case(prefix) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+fn strip_suffix_ignore_case<'a>(text: &'a str, suffix: &str) -> Option<&'a str> {
+    let split = text.len().checked_sub(suffix.len())?;
+    let (rest, tail) = (text.get(..split)?, text.get(split..)?);
+    if tail.eq_ignore_ascii_case(suffix) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+const fn flag(name: &'static str, register: u8, shift: u8) -> Field {
+    Field {
+        name,
+        register,
+        shift,
+        width: 1,
+        encoding: Encoding::Flag,
+    }
+}
+
+const fn field(
+    name: &'static str,
+    register: u8,
+    shift: u8,
+    width: u8,
+    encoding: Encoding,
+) -> Field {
+    Field {
+        name,
+        register,
+        shift,
+        width,
+        encoding,
+    }
+}
+
+/// Every field of every register, ordered by register and then from the most significant bit down
+pub const FIELDS: [Field; 39] = [
+    flag("EN_HIZ", 0x00, 7),
+    field(
+        "VINDPM",
+        0x00,
+        3,
+        4,
+        Encoding::Linear {
+            offset: VINDPM_MIN_MILLIVOLTS,
+            step: VINDPM_STEP_MILLIVOLTS,
+            unit: Unit::Millivolts,
+        },
+    ),
+    field(
+        "IINLIM",
+        0x00,
+        0,
+        3,
+        Encoding::Table {
+            values: &[100, 150, 500, 900, 1200, 1500, 2000, 3000],
+            unit: Unit::Milliamps,
+        },
+    ),
+    flag("REGISTER_RESET", 0x01, 7),
+    flag("I2C_WATCHDOG_TIMER_RESET", 0x01, 6),
+    field(
+        "CHG_CONFIG",
+        0x01,
+        4,
+        2,
+        Encoding::Named(&["disabled", "charge", "otg", "otg-11"]),
+    ),
+    field(
+        "SYS_MIN",
+        0x01,
+        1,
+        3,
+        Encoding::Linear {
+            offset: SYS_MIN_MIN_MILLIVOLTS,
+            step: SYS_MIN_STEP_MILLIVOLTS,
+            unit: Unit::Millivolts,
+        },
+    ),
+    field(
+        "ICHG",
+        0x02,
+        2,
+        6,
+        Encoding::Linear {
+            offset: ICHG_MIN_MILLIAMPS,
+            step: ICHG_STEP_MILLIAMPS,
+            unit: Unit::Milliamps,
+        },
+    ),
+    flag("FORCE_20PCT", 0x02, 0),
+    field(
+        "IPRECHG",
+        0x03,
+        4,
+        4,
+        Encoding::Linear {
+            offset: IPRECHG_ITERM_MIN_MILLIAMPS,
+            step: IPRECHG_ITERM_STEP_MILLIAMPS,
+            unit: Unit::Milliamps,
+        },
+    ),
+    field(
+        "ITERM",
+        0x03,
+        0,
+        4,
+        Encoding::Linear {
+            offset: IPRECHG_ITERM_MIN_MILLIAMPS,
+            step: IPRECHG_ITERM_STEP_MILLIAMPS,
+            unit: Unit::Milliamps,
+        },
+    ),
+    field(
+        "VREG",
+        0x04,
+        2,
+        6,
+        Encoding::Linear {
+            offset: VREG_MIN_MILLIVOLTS,
+            step: VREG_STEP_MILLIVOLTS,
+            unit: Unit::Millivolts,
+        },
+    ),
+    field(
+        "BATLOWV",
+        0x04,
+        1,
+        1,
+        Encoding::Table {
+            values: &[2800, 3000],
+            unit: Unit::Millivolts,
+        },
+    ),
+    field(
+        "VRECHG",
+        0x04,
+        0,
+        1,
+        Encoding::Table {
+            values: &[100, 300],
+            unit: Unit::Millivolts,
+        },
+    ),
+    flag("EN_TERM", 0x05, 7),
+    flag("TERM_STAT", 0x05, 6),
+    field(
+        "WATCHDOG",
+        0x05,
+        4,
+        2,
+        Encoding::Named(&["off", "40s", "80s", "160s"]),
+    ),
+    flag("EN_TIMER", 0x05, 3),
+    field(
+        "CHG_TIMER",
+        0x05,
+        1,
+        2,
+        Encoding::Table {
+            values: &[5, 8, 12, 20],
+            unit: Unit::Hours,
+        },
+    ),
+    field(
+        "TREG",
+        0x06,
+        0,
+        2,
+        Encoding::Table {
+            values: &[60, 80, 100, 120],
+            unit: Unit::Celsius,
+        },
+    ),
+    flag("DPDM_EN", 0x07, 7),
+    flag("TMR2X_EN", 0x07, 6),
+    flag("BATFET_DISABLE", 0x07, 5),
+    flag("INT_MASK_1", 0x07, 1),
+    flag("INT_MASK_0", 0x07, 0),
+    field(
+        "VBUS_STAT",
+        0x08,
+        6,
+        2,
+        Encoding::Named(&["unknown", "usb-host", "adapter-port", "otg"]),
+    ),
+    field(
+        "CHRG_STAT",
+        0x08,
+        4,
+        2,
+        Encoding::Named(&["not-charging", "pre-charge", "fast-charging", "done"]),
+    ),
+    flag("DPM_STAT", 0x08, 3),
+    flag("PG_STAT", 0x08, 2),
+    flag("THERM_STAT", 0x08, 1),
+    flag("VSYS_STAT", 0x08, 0),
+    flag("WATCHDOG_FAULT", 0x09, 7),
+    flag("BOOST_FAULT", 0x09, 6),
+    field(
+        "CHRG_FAULT",
+        0x09,
+        4,
+        2,
+        Encoding::Named(&["normal", "input", "thermal-shutdown", "safety-timer"]),
+    ),
+    flag("BAT_FAULT", 0x09, 3),
+    field(
+        "NTC_FAULT",
+        0x09,
+        0,
+        3,
+        Encoding::Named(&[
+            "normal",
+            "other-001",
+            "other-010",
+            "other-011",
+            "other-100",
+            "cold",
+            "hot",
+            "other-111",
+        ]),
+    ),
+    field("PN", 0x0A, 3, 3, Encoding::Raw),
+    flag("TS_PROFILE", 0x0A, 2),
+    field("DEV_REG", 0x0A, 0, 2, Encoding::Raw),
+];
+
+/// A field that differs between two chip states
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Change {
+    /// The field that changed
+    pub field: &'static Field,
+    /// Value in the first chip state
+    pub old: Value,
+    /// Value in the second chip state
+    pub new: Value,
+}
+
+impl ChargerState {
+    /// Compare every field of this chip state with `other`, yielding the fields that differ in [`FIELDS`](field/constant.FIELDS.html) order. Does NOT do an I2C call.
+    pub fn diff(&self, other: &ChargerState) -> impl Iterator<Item = Change> {
+        let old = self.registers();
+        let new = other.registers();
+        FIELDS.iter().filter_map(move |field| {
+            let register = usize::from(field.register);
+            let (old, new) = (old[register], new[register]);
+            if field.raw(old) == field.raw(new) {
+                None
+            } else {
+                Some(Change {
+                    field,
+                    old: field.decode(old),
+                    new: field.decode(new),
+                })
+            }
+        })
+    }
+}
+
+/// Write every field of a register as `NAME=value`, separated by spaces
+pub(crate) fn fmt_fields(f: &mut fmt::Formatter<'_>, register: u8, bits: u8) -> fmt::Result {
+    let mut fields = FIELDS.iter().filter(|field| field.register == register);
+    if let Some(field) = fields.next() {
+        write!(f, "{}={}", field.name, field.decode(bits))?;
+    }
+    for field in fields {
+        write!(f, " {}={}", field.name, field.decode(bits))?;
+    }
+    Ok(())
+}
+
+/// Add every field of a register to a struct's `Debug` output
+pub(crate) fn debug_fields(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    register: u8,
+    bits: u8,
+) -> fmt::Result {
+    let mut debug = f.debug_struct(name);
+    debug.field("bits", &format_args!("{:#010b}", bits));
+    for field in FIELDS.iter().filter(|field| field.register == register) {
+        debug.field(field.name, &format_args!("{}", field.decode(bits)));
+    }
+    debug.finish()
+}
+
+/// Write every field of a register as `NAME=value`, separated by spaces
+#[cfg(feature = "defmt")]
+pub(crate) fn format_fields(f: defmt::Formatter<'_>, register: u8, bits: u8) {
+    for (i, field) in FIELDS
+        .iter()
+        .filter(|field| field.register == register)
+        .enumerate()
+    {
+        if i != 0 {
+            defmt::write!(f, " ");
+        }
+        defmt::write!(f, "{=str}={}", field.name, field.decode(bits));
+    }
+}
+
+/// Write every register as `Name: NAME=value ...`, separated by semicolons
+#[cfg(feature = "defmt")]
+pub(crate) fn format_registers(f: defmt::Formatter<'_>, registers: &[u8]) {
+    for (address, bits) in registers.iter().enumerate() {
+        if address != 0 {
+            defmt::write!(f, "; ");
+        }
+        defmt::write!(f, "{=str}: ", crate::REGISTER_NAMES[address]);
+        format_fields(f, address as u8, *bits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantities() {
+        assert_eq!(parse_quantity("4.2V", Unit::Millivolts), Some(4200));
+        assert_eq!(parse_quantity("4.2000 v", Unit::Millivolts), Some(4200));
+        assert_eq!(parse_quantity("4200", Unit::Millivolts), Some(4200));
+        assert_eq!(parse_quantity(".5A", Unit::Milliamps), Some(500));
+        assert_eq!(parse_quantity("120°C", Unit::Celsius), Some(120));
+        assert_eq!(parse_quantity("4.2005V", Unit::Millivolts), None);
+        assert_eq!(parse_quantity(".", Unit::Millivolts), None);
+        assert_eq!(parse_quantity("4.2mA", Unit::Millivolts), None);
+    }
+
+    #[test]
+    fn quantities_out_of_range() {
+        assert_eq!(parse_quantity("65.535V", Unit::Millivolts), Some(u16::MAX));
+        assert_eq!(parse_quantity("65.536V", Unit::Millivolts), None);
+        assert_eq!(parse_quantity("65536mV", Unit::Millivolts), None);
+        assert_eq!(parse_quantity("4294967.999V", Unit::Millivolts), None);
+        assert_eq!(parse_quantity("4294968V", Unit::Millivolts), None);
+        assert_eq!(parse_quantity("99999999999mV", Unit::Millivolts), None);
+    }
+}
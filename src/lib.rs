@@ -20,6 +20,12 @@
 //!
 //! The battery field-effect transistor (BATFET) is used to control the flow of current to the battery.
 //! You can manually disable it by writing 1 to [`MiscOperationControl::BATFET_DISABLE`](struct.MiscOperationControl.html#associatedconstant.BATFET_DISABLE). This disconnects the battery, disabling both charging and discharging.
+//! See [`ship`](ship/index.html) for doing this safely.
+//!
+//! # OTG Boost
+//!
+//! Setting [`PowerOnConfiguration::CHG_CONFIG[1:0]`](struct.PowerOnConfiguration.html#associatedconstant.CHG_CONFIG_1) to 10 or 11 turns the buck converter around to supply 5V on VBUS from the battery.
+//! If VBUS is overloaded or goes over-voltage in this mode, [`Fault::BOOST_FAULT`](struct.Fault.html#associatedconstant.BOOST_FAULT) is set. See [`otg`](otg/index.html) for managing a boost session.
 //!
 //! # Power Path Management
 //! [Dynamic Power Management](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A326%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C202.4%2C0%5D) ensures compliance with the USB specification.
@@ -32,6 +38,8 @@
 //!
 //! For example, the default input voltage limit offset is set to 480 mV (3.88V + 480mV = 4.36V), which is the correct voltage for fully charging a Lithium-Ion cell.
 //!
+//! For weak sources such as solar panels, see [`mppt`](mppt/index.html).
+//!
 //! ## Supplement mode
 //!
 //! If the input source is still overloaded when the charge current is dropped to 0A, the chip enters supplement mode; the BATFET is turned on and the battery begins discharging to supplement the input source.
@@ -52,6 +60,8 @@
 //!     * OTG pin = 1 : 500 mA (IINLIM[2:0] = 010)
 //! * Charging Down Stream Port or Dedicated Charging Down Stream Port (CDP/DCP): 1.5A (IINLIM[2:0] = 101)
 //!
+//! Detection can be repeated at any time, see [`detect`](detect/index.html).
+//!
 //! ## HIZ
 //!
 //! When the chip is in high-impedance (HIZ) the buck converter is disabled and the system load is supplied by the battery. To comply with the USB battery charging specification, the chip enters HIZ if the input source is a 100mA USB host and the battery voltage is above VBATGD (3.55V).
@@ -63,6 +73,7 @@
 //! ILIM is a hardware pin for a limiting maximum input current. It is grounded with a resistor using the following formula: `ILIM = 1V / R_ILIM * 530`
 //!
 //! By changing [`InputSourceControl::IINLIM[2:0]`](struct.InputSourceControl.html#associatedconstant.IINLIM_2), you can [ONLY REDUCE the input current limit below ILIM](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A436%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C481.2%2C0%5D).
+//! See [`adaptive`](adaptive/index.html) for finding the highest limit an unknown source can supply.
 //!
 //! ## Charging Profile
 //!
@@ -74,6 +85,8 @@
 //! * Fast Charge: the battery voltage is above [`ChargeVoltageControl::BATLOWV`](struct.ChargeVoltageControl.html#associatedconstant.BATLOWV) (2.8V/3V), and the current limit is set to 512mA + [`ChargeCurrentControl::ICHG[5:0]`](struct.ChargeCurrentControl.html#associatedconstant.ICHG_5)
 //! * Constant-Voltage: the battery voltage has reached the recharge threshold voltage (3.504V + [`ChargeVoltageControl::VREG[5:0]`](struct.ChargeVoltageControl.html#associatedconstant.VREG_5)),and charging current drops rapidly to 128mA + [`PreChargeTerminationCurrentControl::ITERM[3:0]`](struct.PreChargeTerminationCurrentControl.html#associatedconstant.ITERM_3) at which charging is terminated
 //!
+//! See [`profile`](profile/index.html) for computing these limits from a description of the cell, or [`config`](config/index.html) for fixing the whole configuration at compile time.
+//!
 //! ## Battery Temperature
 //!
 //! An external thermistor is used to measure battery temperature. The reading must be between VLTF and VHTF, else the chip will suspend charging. The nature of the thermal fault will be indicated in [`Fault::NTC_FAULT[2:0]`](struct.Fault.html#associatedconstant.NTC_FAULT_2)
@@ -87,6 +100,7 @@
 //! Termination will be disabled if the device is in thermal regulation or input current/voltage regulation. It can also be disabled manually by writing 0 to [`ChargeTerminationTimerControl::EN_TERM`](struct.ChargeTerminationTimerControl.html#associatedconstant.EN_TERM)
 //!
 //! When [`ChargeCurrentControl::FORCE_20PCT`](struct.ChargeCurrentControl.html#associatedconstant.FORCE_20PCT) is set, make sure that the termination current [`PreChargeTerminationCurrentControl::ITERM[3:0]`](struct.PreChargeTerminationCurrentControl.html#associatedconstant.ITERM_3) is less than 20% of the charging current, otherwise charging will not terminate.
+//! [`ChargerState::validate`](struct.ChargerState.html#method.validate) checks for this and other constraints, see [`validate`](validate/index.html).
 //!
 //! Writing 1 to [`ChargeTerminationTimerControl::TERM_STAT`](struct.ChargeTerminationTimerControl.html#associatedconstant.TERM_STAT) will enable an early charge done indication on the STAT pin when charging current falls below 800 mA.
 //!
@@ -123,20 +137,66 @@
 //!
 //! An input voltage of over 18V for VBUS will stop buck mode operation and [`Fault::CHRG_FAULT[1:0]`](struct.Fault.html#associatedconstant.CHRG_FAULT_1) will be set to 01.
 
-#![no_std]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![forbid(unsafe_code)]
 
 extern crate embedded_hal as hal;
 
+use core::ops::RangeInclusive;
 use hal::blocking::i2c::{Write, WriteRead};
 
+pub mod adaptive;
+pub mod calc;
+pub mod config;
+pub mod control;
+pub mod detect;
+#[cfg(feature = "std")]
+pub mod dump;
+pub mod event;
+pub mod field;
+pub mod mppt;
+pub mod otg;
+pub mod profile;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod ship;
+#[cfg(any(feature = "sim", test))]
+pub mod sim;
+pub mod status;
+pub mod storage;
+pub mod telemetry;
+pub mod validate;
+
+#[cfg(feature = "macros")]
+pub use bq24195_i2c_macros::bq24195_config;
+pub use config::ChargerConfig;
+pub use control::{ChargerMode, InputCurrentLimit, InterruptMask, SafetyTimer, Watchdog};
+pub use status::{ChargeFault, ChargeStatus, NtcFault, VbusStatus};
+
 /// I2C Address of BQ24195
 pub const ADDRESS: u8 = 0x6B;
 
+/// Address of the last register that can be written. The registers after it (0x08 - 0x0A) are read-only.
+pub const LAST_WRITABLE_REGISTER: u8 = 0x07;
+
+/// Error from an operation that can fail for reasons other than I2C
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// [`Fault::BOOST_FAULT`](struct.Fault.html#associatedconstant.BOOST_FAULT) was reported while in OTG boost mode
+    BoostFault,
+    /// [`SystemStatus::PG_STAT`](struct.SystemStatus.html#associatedconstant.PG_STAT) reports no good input source
+    NoInputPower,
+    /// The chip did not finish an operation in time
+    Timeout,
+}
+
 macro_rules! registers {
     ($(
             $(#[$outer:meta])*
-            $registerName: ident ($registerAddress: pat) {
+            $registerName: ident ($registerAddress: literal, $access: ident) {
                 #[$bit7meta:meta]
                 $bit7: ident,
                 #[$bit6meta:meta]
@@ -159,11 +219,53 @@ macro_rules! registers {
     ) => {
         paste::item!{
             /// BQ24195 state, as viewed from I2C
-            #[derive(Clone)]
+            ///
+            /// Two chip states are equal if all of their registers are, regardless of staged edits.
+            ///
+            /// With the `serde` feature, human-readable formats name and decode every field, while compact formats store each register as a byte.
+            /// Every register must be present when deserializing, but fields left out of a register take their defaults. Staged edits are not serialized.
+            ///
+            /// ```
+            /// # #[cfg(feature = "serde")]
+            /// # fn main() {
+            /// use bq24195_i2c::ChargerState;
+            ///
+            /// let state: ChargerState = serde_json::from_str(r#"{
+            ///     "input_source_control": { "VINDPM": 4360, "IINLIM": 1500 },
+            ///     "power_on_configuration": {},
+            ///     "charge_current_control": {},
+            ///     "pre_charge_termination_current_control": {},
+            ///     "charge_voltage_control": {},
+            ///     "charge_termination_timer_control": {},
+            ///     "thermal_regulation_control": {},
+            ///     "misc_operation_control": {},
+            ///     "system_status": { "VBUS_STAT": "adapter-port", "CHRG_STAT": "fast-charging", "PG_STAT": true },
+            ///     "fault": {},
+            ///     "vendor_part_revision_status": {}
+            /// }"#).unwrap();
+            /// assert_eq!(state.get_input_source_control().bits(), 0x35);
+            /// assert_eq!(state.get_system_status().bits(), 0b1010_0100);
+            ///
+            /// let json = serde_json::to_string(&state).unwrap();
+            /// assert_eq!(serde_json::from_str::<ChargerState>(&json).unwrap(), state);
+            /// assert!(serde_json::from_str::<ChargerState>(r#"{ "input_source_control": {} }"#).is_err());
+            ///
+            /// let bytes = postcard::to_allocvec(&state).unwrap();
+            /// assert_eq!(bytes, state.registers());
+            /// assert_eq!(postcard::from_bytes::<ChargerState>(&bytes).unwrap(), state);
+            /// # }
+            /// # #[cfg(not(feature = "serde"))]
+            /// # fn main() {}
+            /// ```
+            #[derive(Clone, Debug)]
+            #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
             pub struct ChargerState {
                 $(
                     [<$registerName:snake:lower>]: $registerName,
                 )*
+                /// Bit n is set if register n has a staged edit that has not been written
+                #[cfg_attr(feature = "serde", serde(skip))]
+                staged: u16,
             }
         }
 
@@ -177,31 +279,104 @@ macro_rules! registers {
                         $(
                             [<$registerName:snake:lower>]: $registerName::default(),
                         )*
+                        staged: 0,
                     };
                     state.read_all(i2c)?;
                     Ok(state)
                 }
 
-                /// Read all registers to set the current state of BQ24195.
+                /// Read all registers to set the current state of BQ24195. All staged edits are discarded.
                 pub fn read_all<E, I2C: WriteRead<Error = E>>(&mut self, i2c: &mut I2C) -> Result<(), E> {
+                    self.read_range(i2c, 0x00..=LAST_REGISTER)
+                }
+
+                /// Read a range of consecutive registers in a single transaction, updating the chip state. Staged edits to those registers are discarded.
+                ///
+                /// If an error occurs, the chip state remains the same.
+                ///
+                /// # Panics
+                ///
+                /// If the range is empty or goes past the last register (0x0A).
+                pub fn read_range<E, I2C: WriteRead<Error = E>>(&mut self, i2c: &mut I2C, registers: RangeInclusive<u8>) -> Result<(), E> {
+                    let (start, end) = (usize::from(*registers.start()), usize::from(*registers.end()));
+                    assert!(start <= end && end <= usize::from(LAST_REGISTER), "register range must be within 0x00..=0x0A");
                     let mut values = [0u8; NUM_REGISTERS];
-                    i2c.write_read(ADDRESS, &[0x00], &mut values)?;
+                    // The register address auto-increments after each byte read
+                    i2c.write_read(ADDRESS, &[start as u8], &mut values[start..=end])?;
                     $(
-                        self.[<$registerName:snake:lower>] = values[$registerAddress].into();
+                        if (start..=end).contains(&$registerAddress) {
+                            self.[<$registerName:snake:lower>] = values[$registerAddress].into();
+                        }
                     )*
+                    self.staged &= !(((1u16 << (end - start + 1)) - 1) << start);
                     Ok(())
                 }
 
-                /// Write chip state to all registers. Useful for taking a preset chip state and applying it.
-                ///
-                /// [Relevant BQ24195 Datasheet Section](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A98%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C556.4%2C0%5D)
-                pub fn write_all<E, I2C: Write<Error = E>>(&self, i2c: &mut I2C) -> Result<(), E> {
-                    i2c.write(ADDRESS, &[0x00])?;
+                /// Create a `ChargerState` from the values of all registers, indexed by address, i.e. from a register dump. Does NOT do an I2C call.
+                pub fn from_registers(values: [u8; NUM_REGISTERS]) -> Self {
+                    Self {
+                        $(
+                            [<$registerName:snake:lower>]: values[$registerAddress].into(),
+                        )*
+                        staged: 0,
+                    }
+                }
+
+                /// Values of all registers in the chip state, indexed by address. Does NOT do an I2C call.
+                pub fn registers(&self) -> [u8; NUM_REGISTERS] {
                     let mut values = [0u8; NUM_REGISTERS];
                     $(
                         values[$registerAddress] = self.[<$registerName:snake:lower>].into();
                     )*
-                    i2c.write(ADDRESS, &values[..=LAST_WRITABLE_REGISTER])?;
+                    values
+                }
+
+                /// Write chip state to all registers. Useful for taking a preset chip state and applying it.
+                ///
+                /// Staged edits are written too, and are no longer staged afterwards.
+                ///
+                /// [Relevant BQ24195 Datasheet Section](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A98%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C556.4%2C0%5D)
+                pub fn write_all<E, I2C: Write<Error = E>>(&mut self, i2c: &mut I2C) -> Result<(), E> {
+                    let values = self.registers();
+                    // The first byte of the transaction is the starting register address, which auto-increments
+                    let mut buffer = [0u8; LAST_WRITABLE_REGISTER as usize + 2];
+                    buffer[1..].copy_from_slice(&values[..=usize::from(LAST_WRITABLE_REGISTER)]);
+                    i2c.write(ADDRESS, &buffer)?;
+                    // Only writable registers can be staged, and they were all just written
+                    self.staged = 0;
+                    Ok(())
+                }
+
+                /// True if any register has a staged edit that has not been written
+                pub fn has_staged_changes(&self) -> bool {
+                    self.staged != 0
+                }
+
+                /// Write all staged edits, one transaction per run of consecutive staged registers.
+                /// Registers without staged edits are not written.
+                ///
+                /// If an error occurs, the edits that were not written stay staged.
+                pub fn commit<E, I2C: Write<Error = E>>(&mut self, i2c: &mut I2C) -> Result<(), E> {
+                    let values = self.registers();
+                    let mut start = 0;
+                    while start <= usize::from(LAST_WRITABLE_REGISTER) {
+                        if self.staged & staged_bit(start) == 0 {
+                            start += 1;
+                            continue;
+                        }
+                        let mut end = start;
+                        while end < usize::from(LAST_WRITABLE_REGISTER) && self.staged & staged_bit(end + 1) != 0 {
+                            end += 1;
+                        }
+                        // The first byte of the transaction is the starting register address, which auto-increments
+                        let mut buffer = [0u8; LAST_WRITABLE_REGISTER as usize + 2];
+                        let len = end - start + 1;
+                        buffer[0] = start as u8;
+                        buffer[1..=len].copy_from_slice(&values[start..=end]);
+                        i2c.write(ADDRESS, &buffer[..=len])?;
+                        self.staged &= !(((1u16 << len) - 1) << start);
+                        start = end + 1;
+                    }
                     Ok(())
                 }
 
@@ -211,33 +386,66 @@ macro_rules! registers {
                         self.[<$registerName:snake:lower>]
                     }
 
-                    /// Read the state of a single register over I2C, updating the chip state.
+                    /// Read the state of a single register over I2C, updating the chip state. A staged edit to the register is discarded.
                     ///
                     /// If an error occurs, the chip state remains the same.
                     pub fn [<read_$registerName:snake:lower>]<E, I2C: WriteRead<Error = E>>(&mut self, i2c: &mut I2C) -> Result<(), E> {
                         let mut value = [0u8; 1];
                         i2c.write_read(ADDRESS, &[$registerAddress], &mut value)?;
                         self.[<$registerName:snake:lower>] = value[0].into();
+                        self.staged &= !staged_bit($registerAddress);
                         Ok(())
                     }
 
-                    /// Write the state of a single register over I2C, updating the chip state.
+                    /// Write the state of a single register over I2C, updating the chip state. A staged edit to the register is replaced.
                     ///
                     /// If an error occurs, the chip state remains the same.
                     pub fn [<write_$registerName:snake:lower>]<E, I2C: Write<Error = E>>(&mut self, i2c: &mut I2C, [<$registerName:snake:lower>]: $registerName) -> Result<(), E> {
-                        i2c.write(ADDRESS, &[$registerAddress])?;
-                        let value = [[<$registerName:snake:lower>].into(); 1];
-                        i2c.write(ADDRESS, &value)?;
+                        i2c.write(ADDRESS, &[$registerAddress, [<$registerName:snake:lower>].into()])?;
                         self.[<$registerName:snake:lower>] = [<$registerName:snake:lower>];
+                        self.staged &= !staged_bit($registerAddress);
                         Ok(())
                     }
+
+                    stage_method!($access, $registerName, $registerAddress);
                 )*
             }
         }
 
+        impl PartialEq for ChargerState {
+            fn eq(&self, other: &Self) -> bool {
+                self.registers() == other.registers()
+            }
+        }
+
+        impl Eq for ChargerState {}
+
+        /// Name of each register, indexed by address
+        const REGISTER_NAMES: [&str; NUM_REGISTERS] = [$(stringify!($registerName)),*];
+
+        /// Every register, decoding every field
+        #[cfg(feature = "defmt")]
+        impl defmt::Format for ChargerState {
+            fn format(&self, f: defmt::Formatter<'_>) {
+                field::format_registers(f, &self.registers())
+            }
+        }
+
+        /// One line per register, decoding every field
+        impl core::fmt::Display for ChargerState {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                for (address, bits) in self.registers().iter().enumerate() {
+                    write!(f, "{:#04x} {}: ", address, REGISTER_NAMES[address])?;
+                    field::fmt_fields(f, address as u8, *bits)?;
+                    writeln!(f)?;
+                }
+                Ok(())
+            }
+        }
+
         $(
             $(#[$outer])*
-            #[derive(Copy, Debug, PartialEq, Clone, Eq)]
+            #[derive(Copy, PartialEq, Clone, Eq)]
             pub struct $registerName {
                 bits: u8
             }
@@ -259,6 +467,24 @@ macro_rules! registers {
                 pub const $bit1: Self = Self { bits: 1u8 << 1 };
                 #[$bit0meta]
                 pub const $bit0: Self = Self { bits: 1u8 << 0 };
+
+                /// Power-on default value of the register
+                pub(crate) const DEFAULT: Self = Self { bits: $($registerName::$default.bits |)* 0 };
+
+                /// Raw value of the register
+                pub const fn bits(&self) -> u8 {
+                    self.bits
+                }
+
+                /// Register with the given raw value. Same as `From<u8>`, but usable in `const` context.
+                pub const fn from_bits(bits: u8) -> Self {
+                    Self { bits }
+                }
+
+                /// Returns true if all of the bits set in `other` are also set in this register
+                pub const fn contains(&self, other: Self) -> bool {
+                    self.bits & other.bits == other.bits
+                }
             }
 
             impl core::ops::BitOr for $registerName {
@@ -300,9 +526,52 @@ macro_rules! registers {
                 }
             }
 
+            impl core::ops::Not for $registerName {
+                type Output = Self;
+                fn not(self) -> Self {
+                    Self { bits: !self.bits }
+                }
+            }
+
+            /// Decodes every field, i.e. `EN_HIZ=0 VINDPM=4.36V IINLIM=1500mA`
+            impl core::fmt::Display for $registerName {
+                fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                    field::fmt_fields(f, $registerAddress, self.bits)
+                }
+            }
+
+            #[cfg(feature = "defmt")]
+            impl defmt::Format for $registerName {
+                fn format(&self, f: defmt::Formatter<'_>) {
+                    field::format_fields(f, $registerAddress, self.bits)
+                }
+            }
+
+            /// Decoded fields for human-readable formats, the raw value otherwise
+            #[cfg(feature = "serde")]
+            impl serde::Serialize for $registerName {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serialize::serialize_register(serializer, $registerAddress, self.bits)
+                }
+            }
+
+            /// Fields missing from human-readable formats keep their power-on defaults
+            #[cfg(feature = "serde")]
+            impl<'de> serde::Deserialize<'de> for $registerName {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    serialize::deserialize_register(deserializer, $registerAddress, Self::default().bits).map(Self::from)
+                }
+            }
+
+            impl core::fmt::Debug for $registerName {
+                fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                    field::debug_fields(f, stringify!($registerName), $registerAddress, self.bits)
+                }
+            }
+
             impl Default for $registerName {
                 fn default() -> $registerName {
-                    $($registerName::$default |)* 0u8.into()
+                    Self::DEFAULT
                 }
             }
 
@@ -314,17 +583,40 @@ macro_rules! registers {
                 }
             }
 
-            impl Into<u8> for $registerName {
-                fn into(self) -> u8 {
-                    self.bits
+            impl From<$registerName> for u8 {
+                fn from(register: $registerName) -> u8 {
+                    register.bits
                 }
             }
         )*
     };
 }
 
+/// Generate `stage_*` for a register that can be written. Read-only registers cannot be staged.
+macro_rules! stage_method {
+    (read_write, $registerName: ident, $registerAddress: literal) => {
+        paste::item! {
+            /// Update a register in the chip state and stage it to be written by [`commit`](#method.commit). Does NOT do an I2C call.
+            ///
+            /// Nothing is staged if the value is the same as in the chip state.
+            pub fn [<stage_$registerName:snake:lower>](&mut self, [<$registerName:snake:lower>]: $registerName) {
+                if self.[<$registerName:snake:lower>] != [<$registerName:snake:lower>] {
+                    self.[<$registerName:snake:lower>] = [<$registerName:snake:lower>];
+                    self.staged |= staged_bit($registerAddress);
+                }
+            }
+        }
+    };
+    (read_only, $registerName: ident, $registerAddress: literal) => {};
+}
+
 const NUM_REGISTERS: usize = 11;
-const LAST_WRITABLE_REGISTER: usize = 7;
+const LAST_REGISTER: u8 = NUM_REGISTERS as u8 - 1;
+
+/// Bit for a register address in `ChargerState::staged`
+const fn staged_bit(address: usize) -> u16 {
+    1 << address
+}
 
 registers!(
     /// [Register 0x00](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A578%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C681.2%2C0%5D)
@@ -332,7 +624,7 @@ registers!(
     /// VINDPM[3:0] is added to 3.88V
     ///
     /// IINLIM[2:0] is scaled in an odd manner:
-    /// ```
+    /// ```text
     /// 000 = 100  mA
     /// 001 = 150  mA
     /// 010 = 500  mA
@@ -342,7 +634,7 @@ registers!(
     /// 110 = 2000 mA
     /// 111 = 3000 mA
     /// ```
-    InputSourceControl (0x00) {
+    InputSourceControl (0x00, read_write) {
         /// Buck Converter Control (0 = Restart Buck Converter, 1 = Buck Converter Stops, system load supplied by battery)
         EN_HIZ,
         /// Input Voltage Limit Offset Bit 3: 640mV
@@ -363,7 +655,7 @@ registers!(
     /// [Register 0x01](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A586%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C720%2C0%5D)
     ///
     /// SYS_MIN[2:0] is added to 3.0V
-    PowerOnConfiguration (0x01) {
+    PowerOnConfiguration (0x01, read_write) {
         /// Resets this register upon writing this value, returns to 0 after reset
         REGISTER_RESET,
         /// Reset I2C watchdog timer, returns to 0 after reset
@@ -389,7 +681,7 @@ registers!(
     /// [Register 0x02](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A158%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C720%2C0%5D)
     ///
     /// ICHG[5:0] is added to 512mA
-    ChargeCurrentControl (0x02) {
+    ChargeCurrentControl (0x02, read_write) {
         /// Fast Charge Current Limit Offset Bit 5: 2048 mA
         ICHG_5,
         /// Fast Charge Current Limit Offset Bit 4: 1024 mA
@@ -412,7 +704,7 @@ registers!(
     /// IPRECHG[3:0] is added to 128mA
     ///
     /// ITERM[3:0] is added to 128mA
-    PreChargeTerminationCurrentControl (0x03) {
+    PreChargeTerminationCurrentControl (0x03, read_write) {
         /// Pre-Charge Current Limit Offset Bit 3: 1024 mA
         IPRECHG_3,
         /// Pre-Charge Current Limit Offset Bit 2: 512 mA
@@ -433,7 +725,7 @@ registers!(
     /// [Register 0x04](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A601%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C720%2C0%5D)
     ///
     /// VREG[5:0] is added to 3.504V
-    ChargeVoltageControl (0x04) {
+    ChargeVoltageControl (0x04, read_write) {
         /// Charger Voltage Limit Offset Bit 5: 512mV
         VREG_5,
         /// Charger Voltage Limit Offset Bit 4: 256mV
@@ -457,7 +749,7 @@ registers!(
         BATLOWV
     }},
     /// [Register 0x05](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A601%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C437.5%2C0%5D)
-    ChargeTerminationTimerControl (0x05) {
+    ChargeTerminationTimerControl (0x05, read_write) {
         /// Charging Termination Enable
         EN_TERM,
         /// Termination Indicator Threshold
@@ -481,7 +773,7 @@ registers!(
         CHG_TIMER_0
     }},
     /// [Register 0x06](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A609%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C720%2C0%5D)
-    ThermalRegulationControl (0x06) {
+    ThermalRegulationControl (0x06, read_write) {
         /// Reserved, must write 0
         RESERVED_7,
         /// Reserved, must write 0
@@ -500,7 +792,7 @@ registers!(
         TREG_0,
     Default { TREG_1, TREG_0 }},
     /// [Register 0x07](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A609%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C463.9%2C0%5D)
-    MiscOperationControl (0x07) {
+    MiscOperationControl (0x07, read_write) {
         /// Force DPDM detection
         DPDM_EN,
         /// Safety Timer Setting during Input DPM and Thermal Regulation (1 = safety timer slowed by 2x, 0 = normal speed)
@@ -513,9 +805,9 @@ registers!(
         RESERVED_3,
         /// Reserved, must write 0
         RESERVED_2,
-        /// Interrupt Mask Bit 1 (1 = interrupt on CHRG_FAULT)
+        /// Interrupt Mask Bit 1 (0 = no INT on CHRG_FAULT, 1 = INT on CHRG_FAULT)
         INT_MASK_1,
-        /// Interrupt Mask Bit 0 (0 = interrupt on BAT_FAULT)
+        /// Interrupt Mask Bit 0 (0 = no INT on BAT_FAULT, 1 = INT on BAT_FAULT)
         INT_MASK_0,
     Default {
         TMR2X_EN,
@@ -524,7 +816,7 @@ registers!(
         INT_MASK_0
     }},
     /// [Register 0x08](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A618%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C720%2C0%5D)
-    SystemStatus (0x08) {
+    SystemStatus (0x08, read_only) {
         /// VBUS Status Bit 1 (10 = Adapter port, 11 = OTG)
         VBUS_STAT_1,
         /// VBUS Status Bit 0 (00 = Unknown, 01 = USB host)
@@ -543,11 +835,11 @@ registers!(
         VSYS_STAT,
     Default {}},
     /// [Register 0x09](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A618%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C468.1%2C0%5D)
-    Fault (0x09) {
+    Fault (0x09, read_only) {
         /// Watchdog Fault Status (0 = normal, 1 = watchdog timer expired)
         WATCHDOG_FAULT,
-        /// Reserved, always 0
-        RESERVED,
+        /// Boost Fault Status (0 = normal, 1 = VBUS overloaded in OTG, VBUS OVP, or battery too low in boost mode)
+        BOOST_FAULT,
         /// Charging Fault Bit 1 (10 = Thermal shutdown, 11 = Charge Safety Timer Expiration)
         CHRG_FAULT_1,
         /// Charging Fault Bit 0 (00 = Normal, 01 = Input Fault (VBUS OVP or VBAT < VBUS < 3.8V))
@@ -562,7 +854,7 @@ registers!(
         NTC_FAULT_0,
     Default { WATCHDOG_FAULT }},
     /// [Register 0x0A](https://www.ti.com/lit/ds/symlink/bq24195l.pdf#%5B%7B%22num%22%3A626%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C0%2C720%2C0%5D)
-    VendorPartRevisionStatus (0x0A) {
+    VendorPartRevisionStatus (0x0A, read_only) {
         /// Reserved, always 0
         RESERVED_7,
         /// Reserved, always 0
@@ -585,3 +877,62 @@ registers!(
         DEV_REG_1
     }}
 );
+
+impl Fault {
+    /// Bit 6 was previously modelled as reserved
+    #[deprecated(note = "bit 6 is BOOST_FAULT")]
+    pub const RESERVED: Self = Self::BOOST_FAULT;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bus that records every write transaction
+    #[derive(Default)]
+    struct Recorder {
+        writes: Vec<Vec<u8>>,
+    }
+
+    impl Write for Recorder {
+        type Error = ();
+
+        fn write(&mut self, _: u8, bytes: &[u8]) -> Result<(), ()> {
+            self.writes.push(bytes.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn commit_writes_runs_of_staged_registers() {
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        state.stage_input_source_control(InputSourceControl::from(0x30));
+        state.stage_power_on_configuration(PowerOnConfiguration::from(0x1B));
+        state.stage_misc_operation_control(MiscOperationControl::from(0x4B));
+        let mut i2c = Recorder::default();
+        state.commit(&mut i2c).unwrap();
+        assert_eq!(i2c.writes, [vec![0x00, 0x30, 0x1B], vec![0x07, 0x4B]]);
+        assert!(!state.has_staged_changes());
+    }
+
+    #[test]
+    fn unchanged_value_is_not_staged() {
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        state.stage_input_source_control(InputSourceControl::from(0));
+        assert!(!state.has_staged_changes());
+        let mut i2c = Recorder::default();
+        state.commit(&mut i2c).unwrap();
+        assert!(i2c.writes.is_empty());
+    }
+
+    #[test]
+    fn write_all_clears_staged_edits() {
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        state.stage_misc_operation_control(MiscOperationControl::from(0x4B));
+        let mut i2c = Recorder::default();
+        state.write_all(&mut i2c).unwrap();
+        assert!(!state.has_staged_changes());
+        state.commit(&mut i2c).unwrap();
+        assert_eq!(i2c.writes, [vec![0x00, 0, 0, 0, 0, 0, 0, 0, 0x4B]]);
+    }
+}
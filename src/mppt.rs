@@ -0,0 +1,337 @@
+//! Input voltage limit tracking for solar panels and other weak sources.
+//!
+//! A solar panel delivers the most power at a particular voltage (the maximum power point), which moves with light and temperature.
+//! If the charger draws more than the panel can supply, the panel voltage collapses toward 0 and power drops off.
+//! [`InputSourceControl::VINDPM[3:0]`](../struct.InputSourceControl.html#associatedconstant.VINDPM_3) keeps the input from being pulled below a set voltage,
+//! so moving it over time keeps the panel near its maximum power point.
+//!
+//! [`VindpmTracker`](struct.VindpmTracker.html) does this in one of two ways:
+//!
+//! * With an [`InputMeter`](trait.InputMeter.html) measuring input voltage and current, it perturbs VINDPM by one step per update and keeps going in whichever direction increased input power.
+//! * Without one, it only has [`SystemStatus`](../struct.SystemStatus.html) to go on. VINDPM is raised whenever the input collapses (power not good or an input fault),
+//!   held while DPM is active, and lowered while DPM is inactive. This avoids collapse but cannot find the true maximum power point.
+
+use crate::control::{VINDPM_MAX_MILLIVOLTS, VINDPM_MIN_MILLIVOLTS, VINDPM_STEP_MILLIVOLTS};
+use crate::status::ChargeFault;
+use crate::ChargerState;
+use core::convert::Infallible;
+use hal::blocking::delay::DelayMs;
+use hal::blocking::i2c::{Write, WriteRead};
+
+/// A single measurement of the input source
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputReading {
+    /// Input voltage in mV
+    pub millivolts: u16,
+    /// Input current in mA
+    pub milliamps: u16,
+}
+
+impl InputReading {
+    /// Input power in uW
+    pub fn microwatts(&self) -> u32 {
+        u32::from(self.millivolts) * u32::from(self.milliamps)
+    }
+}
+
+/// External measurement of input voltage and current, i.e. from an ADC or a current sense amplifier
+pub trait InputMeter {
+    /// Error produced while measuring
+    type Error;
+
+    /// Measure the input source, or return `None` if no measurement is available
+    fn read(&mut self) -> Result<Option<InputReading>, Self::Error>;
+}
+
+/// Placeholder for trackers without an [`InputMeter`](trait.InputMeter.html)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NoMeter;
+
+impl InputMeter for NoMeter {
+    type Error = Infallible;
+
+    fn read(&mut self) -> Result<Option<InputReading>, Self::Error> {
+        Ok(None)
+    }
+}
+
+/// Options for a [`VindpmTracker`](struct.VindpmTracker.html)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackerConfig {
+    /// Amount VINDPM is moved by in each update, in mV. Rounded down to a multiple of 80mV, with a minimum of 80mV.
+    pub step_millivolts: u16,
+    /// Time between updates in ms, used by [`VindpmTracker::wait_and_update`](struct.VindpmTracker.html#method.wait_and_update)
+    pub interval_ms: u16,
+    /// Lowest VINDPM to use in mV. Rounded up to the next VINDPM setting.
+    pub min_millivolts: u16,
+    /// Highest VINDPM to use in mV. Rounded down to the previous VINDPM setting, and raised to the minimum if below it.
+    pub max_millivolts: u16,
+}
+
+impl Default for TrackerConfig {
+    /// One 80mV step per second over the full VINDPM range
+    fn default() -> Self {
+        Self {
+            step_millivolts: VINDPM_STEP_MILLIVOLTS,
+            interval_ms: 1000,
+            min_millivolts: VINDPM_MIN_MILLIVOLTS,
+            max_millivolts: VINDPM_MAX_MILLIVOLTS,
+        }
+    }
+}
+
+impl TrackerConfig {
+    /// The options with every voltage on a VINDPM setting, so that the tracker can tell when a limit is reached
+    fn rounded(self) -> Self {
+        let setting = |millivolts: u16, round_up: bool| {
+            let offset = millivolts.clamp(VINDPM_MIN_MILLIVOLTS, VINDPM_MAX_MILLIVOLTS)
+                - VINDPM_MIN_MILLIVOLTS;
+            let steps = if round_up {
+                offset.div_ceil(VINDPM_STEP_MILLIVOLTS)
+            } else {
+                offset / VINDPM_STEP_MILLIVOLTS
+            };
+            VINDPM_MIN_MILLIVOLTS + steps * VINDPM_STEP_MILLIVOLTS
+        };
+        let min_millivolts = setting(self.min_millivolts, true);
+        Self {
+            step_millivolts: (self.step_millivolts / VINDPM_STEP_MILLIVOLTS).max(1)
+                * VINDPM_STEP_MILLIVOLTS,
+            interval_ms: self.interval_ms,
+            min_millivolts,
+            max_millivolts: setting(self.max_millivolts, false).max(min_millivolts),
+        }
+    }
+}
+
+/// Error from a [`VindpmTracker`](struct.VindpmTracker.html) update
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TrackerError<I, M> {
+    /// I2C bus error
+    I2c(I),
+    /// Input meter error
+    Meter(M),
+}
+
+/// Adjusts [`InputSourceControl::VINDPM[3:0]`](../struct.InputSourceControl.html#associatedconstant.VINDPM_3) to keep a weak source near its maximum power point
+pub struct VindpmTracker<M> {
+    config: TrackerConfig,
+    meter: M,
+    increasing: bool,
+    last_microwatts: Option<u32>,
+}
+
+impl VindpmTracker<NoMeter> {
+    /// Create a tracker that only uses [`SystemStatus`](../struct.SystemStatus.html)
+    pub fn new(config: TrackerConfig) -> Self {
+        Self::with_meter(config, NoMeter)
+    }
+}
+
+impl<M: InputMeter> VindpmTracker<M> {
+    /// Create a tracker that uses `meter` to find the maximum power point
+    pub fn with_meter(config: TrackerConfig, meter: M) -> Self {
+        Self {
+            config: config.rounded(),
+            meter,
+            increasing: true,
+            last_microwatts: None,
+        }
+    }
+
+    /// Current options, with the voltages rounded to VINDPM settings
+    pub fn config(&self) -> TrackerConfig {
+        self.config
+    }
+
+    /// Release the input meter
+    pub fn release(self) -> M {
+        self.meter
+    }
+
+    /// Wait for [`TrackerConfig::interval_ms`](struct.TrackerConfig.html#structfield.interval_ms), then [`update`](#method.update)
+    pub fn wait_and_update<E, I2C, D>(
+        &mut self,
+        charger: &mut ChargerState,
+        i2c: &mut I2C,
+        delay: &mut D,
+    ) -> Result<u16, TrackerError<E, M::Error>>
+    where
+        I2C: Write<Error = E> + WriteRead<Error = E>,
+        D: DelayMs<u16>,
+    {
+        delay.delay_ms(self.config.interval_ms);
+        self.update(charger, i2c)
+    }
+
+    /// Measure the input, move VINDPM by at most one step, and return the new VINDPM in mV
+    pub fn update<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        charger: &mut ChargerState,
+        i2c: &mut I2C,
+    ) -> Result<u16, TrackerError<E, M::Error>> {
+        charger.read_status(i2c).map_err(TrackerError::I2c)?;
+        charger
+            .read_input_source_control(i2c)
+            .map_err(TrackerError::I2c)?;
+        let status = charger.get_system_status();
+        let collapsed =
+            !status.power_good() || charger.get_fault().charge_fault() == ChargeFault::InputFault;
+
+        let direction = if collapsed {
+            // Whatever the meter says, the source can't keep up at this voltage
+            self.last_microwatts = None;
+            self.increasing = true;
+            Some(true)
+        } else if let Some(reading) = self.meter.read().map_err(TrackerError::Meter)? {
+            let microwatts = reading.microwatts();
+            if let Some(last_microwatts) = self.last_microwatts {
+                if microwatts < last_microwatts {
+                    self.increasing = !self.increasing;
+                }
+            }
+            self.last_microwatts = Some(microwatts);
+            Some(self.increasing)
+        } else if status.dpm() {
+            None
+        } else {
+            Some(false)
+        };
+
+        let current = charger.get_input_source_control().vindpm_millivolts();
+        let step = self.config.step_millivolts;
+        let target = match direction {
+            Some(true) => current.saturating_add(step),
+            Some(false) => current.saturating_sub(step),
+            None => current,
+        }
+        .min(self.config.max_millivolts)
+        .max(self.config.min_millivolts);
+        if target == current {
+            if let Some(increasing) = direction {
+                // At a limit, so the next perturbation has to go the other way
+                self.increasing = !increasing;
+            }
+        } else {
+            charger
+                .write_vindpm_millivolts(i2c, target)
+                .map_err(TrackerError::I2c)?;
+        }
+        Ok(charger.get_input_source_control().vindpm_millivolts())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{ScriptedBus, SimError, SimulatedCharger};
+    use crate::NUM_REGISTERS;
+    use std::collections::VecDeque;
+
+    const POWER_GOOD: u8 = 0b0000_0100;
+    const DPM: u8 = 0b0000_1000;
+
+    /// Meter that reports a 5V input drawing each of the currents in turn
+    struct Readings(VecDeque<u16>);
+
+    impl InputMeter for Readings {
+        type Error = Infallible;
+
+        fn read(&mut self) -> Result<Option<InputReading>, Infallible> {
+            Ok(Some(InputReading {
+                millivolts: 5000,
+                milliamps: self.0.pop_front().expect("no more readings"),
+            }))
+        }
+    }
+
+    /// Bus with the given VINDPM setting and a good input source
+    fn bus(vindpm: u8) -> ScriptedBus<'static> {
+        let mut sim = SimulatedCharger::new();
+        sim.set_register(0x00, vindpm << 3);
+        sim.set_register(0x08, POWER_GOOD);
+        ScriptedBus::new(sim)
+    }
+
+    fn updates<M: InputMeter>(
+        tracker: &mut VindpmTracker<M>,
+        i2c: &mut ScriptedBus,
+        count: usize,
+    ) -> Vec<Result<u16, TrackerError<SimError, M::Error>>> {
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        (0..count)
+            .map(|_| tracker.update(&mut state, i2c))
+            .collect()
+    }
+
+    #[test]
+    fn reverses_when_power_drops() {
+        let mut i2c = bus(6);
+        let meter = Readings([1000, 1200, 1100].into());
+        let mut tracker = VindpmTracker::with_meter(TrackerConfig::default(), meter);
+        assert_eq!(
+            updates(&mut tracker, &mut i2c, 3),
+            [Ok(4440), Ok(4520), Ok(4440)]
+        );
+        assert_eq!(i2c.writes, [[0x00, 0x38], [0x00, 0x40], [0x00, 0x38]]);
+    }
+
+    #[test]
+    fn reverses_at_max() {
+        let mut i2c = bus(6);
+        let config = TrackerConfig {
+            max_millivolts: 4440,
+            ..TrackerConfig::default()
+        };
+        let mut tracker = VindpmTracker::with_meter(config, Readings([1000; 3].into()));
+        assert_eq!(
+            updates(&mut tracker, &mut i2c, 3),
+            [Ok(4440), Ok(4440), Ok(4360)]
+        );
+        assert_eq!(i2c.writes, [[0x00, 0x38], [0x00, 0x30]]);
+    }
+
+    #[test]
+    fn reverses_at_min() {
+        let mut i2c = bus(0);
+        let meter = Readings([1000, 900, 900, 900].into());
+        let mut tracker = VindpmTracker::with_meter(TrackerConfig::default(), meter);
+        assert_eq!(
+            updates(&mut tracker, &mut i2c, 4),
+            [Ok(3960), Ok(3880), Ok(3880), Ok(3960)]
+        );
+        assert_eq!(i2c.writes, [[0x00, 0x08], [0x00, 0x00], [0x00, 0x08]]);
+    }
+
+    #[test]
+    fn steps_up_after_collapse() {
+        let mut i2c = bus(6);
+        let meter = Readings([1000, 500].into());
+        let mut tracker = VindpmTracker::with_meter(TrackerConfig::default(), meter);
+        assert_eq!(updates(&mut tracker, &mut i2c, 1), [Ok(4440)]);
+        i2c.sim.set_register(0x08, 0);
+        assert_eq!(updates(&mut tracker, &mut i2c, 1), [Ok(4520)]);
+        // The reading from before the collapse is forgotten, so less power does not reverse
+        i2c.sim.set_register(0x08, POWER_GOOD);
+        assert_eq!(updates(&mut tracker, &mut i2c, 1), [Ok(4600)]);
+        assert!(tracker.release().0.is_empty());
+    }
+
+    #[test]
+    fn dpm_only_holds_lowers_and_raises() {
+        let mut i2c = bus(6);
+        let mut tracker = VindpmTracker::new(TrackerConfig::default());
+        assert_eq!(updates(&mut tracker, &mut i2c, 1), [Ok(4280)]);
+        i2c.sim.set_register(0x08, POWER_GOOD | DPM);
+        assert_eq!(updates(&mut tracker, &mut i2c, 1), [Ok(4280)]);
+        i2c.sim.set_register(0x08, 0);
+        assert_eq!(updates(&mut tracker, &mut i2c, 1), [Ok(4360)]);
+        assert_eq!(i2c.writes, [[0x00, 0x28], [0x00, 0x30]]);
+    }
+}
@@ -0,0 +1,156 @@
+//! OTG boost sessions.
+//!
+//! [`ChargerState::start_otg`](../struct.ChargerState.html#method.start_otg) switches the chip into boost mode and returns an [`OtgSession`](struct.OtgSession.html).
+//! Call [`OtgSession::poll`](struct.OtgSession.html#method.poll) periodically (or when INT fires) to watch for [`Fault::BOOST_FAULT`](../struct.Fault.html#associatedconstant.BOOST_FAULT).
+//! If it is reported, boost is turned off and the previous charger configuration is restored.
+//! Otherwise, [`OtgSession::end`](struct.OtgSession.html#method.end) restores it once VBUS no longer needs to be supplied.
+//! Dropping a session without ending it leaves the chip in boost mode.
+
+use crate::control::ChargerMode;
+use crate::{ChargerState, Error};
+use hal::blocking::i2c::{Write, WriteRead};
+
+/// An active OTG boost session, holding the charger configuration to restore when it ends.
+///
+/// Dropping the session does NOT restore the previous charger configuration, since that needs the I2C bus.
+/// Call [`end`](#method.end) instead.
+#[must_use = "dropping an OtgSession leaves the chip in boost mode, call end to restore the previous charger configuration"]
+pub struct OtgSession<'a> {
+    charger: &'a mut ChargerState,
+    restore: ChargerMode,
+    active: bool,
+    /// A boost fault was read but the previous configuration is not restored yet
+    faulted: bool,
+}
+
+impl ChargerState {
+    /// Clear any latched fault, then switch to OTG boost mode.
+    ///
+    /// When the session ends, the previous charger configuration is restored. If the chip was already in OTG mode, charging is enabled instead.
+    pub fn start_otg<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+    ) -> Result<OtgSession<'_>, E> {
+        self.read_fault(i2c)?;
+        let previous = self.enable_otg(i2c)?;
+        Ok(OtgSession {
+            charger: self,
+            restore: match previous {
+                ChargerMode::Otg => ChargerMode::Charge,
+                other => other,
+            },
+            active: true,
+            faulted: false,
+        })
+    }
+}
+
+impl OtgSession<'_> {
+    /// True until a boost fault is reported
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Charger configuration that will be restored when the session ends
+    pub fn restore_mode(&self) -> ChargerMode {
+        self.restore
+    }
+
+    /// Read [`Fault`](../struct.Fault.html) and check for a boost fault.
+    ///
+    /// On a boost fault, boost is turned off, the previous charger configuration is restored, and [`Error::BoostFault`](../enum.Error.html#variant.BoostFault) is returned.
+    /// The session is then no longer active and further polls do nothing.
+    /// If restoring fails, the session stays active and the next poll retries it, since the latched fault has already been read.
+    pub fn poll<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+    ) -> Result<(), Error<E>> {
+        if !self.active {
+            return Ok(());
+        }
+        if !self.faulted {
+            self.charger.read_fault(i2c).map_err(Error::I2c)?;
+            self.faulted = self.charger.get_fault().boost_fault();
+        }
+        if self.faulted {
+            self.charger
+                .write_charger_mode(i2c, self.restore)
+                .map_err(Error::I2c)?;
+            self.active = false;
+            return Err(Error::BoostFault);
+        }
+        Ok(())
+    }
+
+    /// Turn off boost and restore the previous charger configuration, if a fault has not already done so
+    pub fn end<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        self,
+        i2c: &mut I2C,
+    ) -> Result<(), E> {
+        if self.active {
+            self.charger.write_charger_mode(i2c, self.restore)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{ScriptedBus, SimulatedCharger};
+    use crate::{Fault, NUM_REGISTERS};
+
+    fn bus(power_on_configuration: u8) -> ScriptedBus<'static> {
+        let mut sim = SimulatedCharger::new();
+        sim.set_register(0x01, power_on_configuration);
+        ScriptedBus::new(sim)
+    }
+
+    #[test]
+    fn session_boosts_then_restores_charging() {
+        let mut i2c = bus(0x1B);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        let mut session = state.start_otg(&mut i2c).unwrap();
+        assert!(session.is_active());
+        assert_eq!(session.restore_mode(), ChargerMode::Charge);
+        assert_eq!(session.poll(&mut i2c), Ok(()));
+        session.end(&mut i2c).unwrap();
+        assert_eq!(i2c.writes, [[0x01, 0x2B], [0x01, 0x1B]]);
+    }
+
+    #[test]
+    fn session_restores_disabled_charging() {
+        let mut i2c = bus(0x0B);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        let session = state.start_otg(&mut i2c).unwrap();
+        assert_eq!(session.restore_mode(), ChargerMode::ChargeDisabled);
+        session.end(&mut i2c).unwrap();
+        assert_eq!(i2c.writes, [[0x01, 0x2B], [0x01, 0x0B]]);
+    }
+
+    #[test]
+    fn session_started_in_otg_restores_charging() {
+        let mut i2c = bus(0x2B);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        let session = state.start_otg(&mut i2c).unwrap();
+        assert_eq!(session.restore_mode(), ChargerMode::Charge);
+        session.end(&mut i2c).unwrap();
+        assert_eq!(i2c.writes, [[0x01, 0x2B], [0x01, 0x1B]]);
+    }
+
+    #[test]
+    fn boost_fault_ends_session() {
+        let mut i2c = bus(0x1B);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        let mut session = state.start_otg(&mut i2c).unwrap();
+        i2c.sim.set_register(0x09, Fault::BOOST_FAULT.bits());
+        assert_eq!(session.poll(&mut i2c), Err(Error::BoostFault));
+        assert!(!session.is_active());
+        assert_eq!(i2c.writes, [[0x01, 0x2B], [0x01, 0x1B]]);
+
+        // Boost is already off, so nothing else is written
+        assert_eq!(session.poll(&mut i2c), Ok(()));
+        session.end(&mut i2c).unwrap();
+        assert_eq!(i2c.writes.len(), 2);
+    }
+}
@@ -0,0 +1,368 @@
+//! Battery profiles: describe a cell and compute the charge registers for it.
+//!
+//! [`BatteryProfile::registers`](struct.BatteryProfile.html#method.registers) turns a cell description into
+//! [`ChargeCurrentControl`](../struct.ChargeCurrentControl.html), [`PreChargeTerminationCurrentControl`](../struct.PreChargeTerminationCurrentControl.html),
+//! [`ChargeVoltageControl`](../struct.ChargeVoltageControl.html) and a [`SafetyTimer`](../control/enum.SafetyTimer.html).
+//! Values are always rounded in the direction that is safe for the cell: voltages and charge currents down, termination current up.
+//! Cells the chip cannot charge within their limits are rejected with a [`ProfileError`](enum.ProfileError.html).
+//!
+//! Presets are provided for common cells. They are applied to the chip state and then written with [`ChargerState::commit`](../struct.ChargerState.html#method.commit):
+//!
+//! ```
+//! use bq24195_i2c::{profile::BatteryProfile, ChargerState};
+//! use embedded_hal::blocking::i2c::Write;
+//!
+//! fn configure_18650<E, I2C: Write<Error = E>>(state: &mut ChargerState, i2c: &mut I2C) -> Result<(), E> {
+//!     let registers = BatteryProfile::li_ion_4v2(2600)
+//!         .registers()
+//!         .expect("2600mAh Li-Ion cell is supported");
+//!     state.apply_profile(&registers);
+//!     state.commit(i2c)
+//! }
+//! ```
+//!
+//! Fast charge currents below 512mA are reached with [`ChargeCurrentControl::FORCE_20PCT`](../struct.ChargeCurrentControl.html#associatedconstant.FORCE_20PCT),
+//! which also halves the pre-charge current.
+
+use crate::control::{
+    BatteryLowVoltage, RechargeThreshold, SafetyTimer, ICHG_MAX_MILLIAMPS, ICHG_MIN_MILLIAMPS,
+    IPRECHG_ITERM_MAX_MILLIAMPS, IPRECHG_ITERM_MIN_MILLIAMPS, IPRECHG_ITERM_STEP_MILLIAMPS,
+    VREG_MIN_MILLIVOLTS,
+};
+use crate::{
+    ChargeCurrentControl, ChargeVoltageControl, ChargerState, PreChargeTerminationCurrentControl,
+};
+
+/// Lowest battery temperature in °C at which the chip will charge, using the cold/hot window and the recommended thermistor network
+pub const CHARGE_TEMPERATURE_MIN_CELSIUS: i8 = 0;
+/// Highest battery temperature in °C at which the chip will charge, using the cold/hot window and the recommended thermistor network
+pub const CHARGE_TEMPERATURE_MAX_CELSIUS: i8 = 45;
+
+/// Cell chemistry
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Chemistry {
+    /// Lithium-Ion or Lithium-Polymer, including high voltage cells
+    LithiumIon,
+    /// Lithium Iron Phosphate (LiFePO4)
+    LithiumIronPhosphate,
+}
+
+/// Description of a single cell
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BatteryProfile {
+    /// Cell chemistry
+    pub chemistry: Chemistry,
+    /// Capacity in mAh
+    pub capacity_mah: u16,
+    /// Maximum charge voltage in mV
+    pub max_charge_voltage_mv: u16,
+    /// Maximum fast charge rate as a percentage of capacity, i.e. 50 for 0.5C
+    pub max_charge_rate_percent: u16,
+    /// Maximum pre-charge current in mA
+    pub precharge_current_ma: u16,
+    /// Minimum termination current in mA
+    pub termination_current_ma: u16,
+    /// Lowest temperature in °C the cell can be charged at
+    pub min_charge_temperature_c: i8,
+    /// Highest temperature in °C the cell can be charged at
+    pub max_charge_temperature_c: i8,
+}
+
+/// Reason a [`BatteryProfile`](struct.BatteryProfile.html) cannot be charged by BQ24195
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProfileError {
+    /// Capacity is 0
+    ZeroCapacity,
+    /// Maximum charge voltage is below the lowest VREG, 3504mV
+    ChargeVoltageTooLow,
+    /// Maximum fast charge current is below the lowest ICHG with FORCE_20PCT, 20% of 512mA
+    ChargeCurrentTooLow,
+    /// Maximum pre-charge current is below the lowest IPRECHG, 128mA (or 64mA with FORCE_20PCT)
+    PrechargeCurrentTooLow,
+    /// Termination current is above the highest ITERM, 2048mA, or not below the fast charge current
+    TerminationCurrentTooHigh,
+    /// The cell's temperature window does not cover the chip's 0°C - 45°C charging window
+    TemperatureWindow,
+}
+
+/// Registers computed from a [`BatteryProfile`](struct.BatteryProfile.html)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileRegisters {
+    /// Fast charge current and FORCE_20PCT
+    pub charge_current_control: ChargeCurrentControl,
+    /// Pre-charge and termination currents
+    pub pre_charge_termination_current_control: PreChargeTerminationCurrentControl,
+    /// Charge voltage, pre-charge threshold and recharge threshold
+    pub charge_voltage_control: ChargeVoltageControl,
+    /// Fast charge safety timer
+    pub safety_timer: SafetyTimer,
+}
+
+impl BatteryProfile {
+    /// Standard 4.2V Lithium-Ion or Lithium-Polymer cell.
+    ///
+    /// Charged at 0.5C up to 4.192V (VREG rounded down from 4.2V) with C/10 pre-charge and C/20 termination.
+    /// Fast charge starts at 3.0V and recharge at 100mV below VREG.
+    ///
+    /// Pre-charge is raised to the lowest current the chip supports if C/10 is below it: 64mA for cells under 1024mAh (charged with FORCE_20PCT), 128mA otherwise.
+    /// Termination is raised to 128mA in the same way, so cells under 282mAh are rejected with [`ProfileError::TerminationCurrentTooHigh`](enum.ProfileError.html#variant.TerminationCurrentTooHigh).
+    pub const fn li_ion_4v2(capacity_mah: u16) -> Self {
+        const MAX_CHARGE_RATE_PERCENT: u16 = 50;
+        let force_20pct = (capacity_mah as u32) * (MAX_CHARGE_RATE_PERCENT as u32) / 100
+            < ICHG_MIN_MILLIAMPS as u32;
+        let min_precharge_current_ma = if force_20pct {
+            IPRECHG_ITERM_MIN_MILLIAMPS / 2
+        } else {
+            IPRECHG_ITERM_MIN_MILLIAMPS
+        };
+        let precharge_current_ma = if capacity_mah / 10 < min_precharge_current_ma {
+            min_precharge_current_ma
+        } else {
+            capacity_mah / 10
+        };
+        Self {
+            chemistry: Chemistry::LithiumIon,
+            capacity_mah,
+            max_charge_voltage_mv: 4200,
+            max_charge_rate_percent: MAX_CHARGE_RATE_PERCENT,
+            precharge_current_ma,
+            termination_current_ma: capacity_mah / 20,
+            min_charge_temperature_c: CHARGE_TEMPERATURE_MIN_CELSIUS,
+            max_charge_temperature_c: CHARGE_TEMPERATURE_MAX_CELSIUS,
+        }
+    }
+
+    /// High voltage 4.35V Lithium-Ion or Lithium-Polymer cell.
+    ///
+    /// Same as [`li_ion_4v2`](#method.li_ion_4v2), but charged up to 4.336V (VREG rounded down from 4.35V).
+    pub const fn li_ion_4v35(capacity_mah: u16) -> Self {
+        Self {
+            max_charge_voltage_mv: 4350,
+            ..Self::li_ion_4v2(capacity_mah)
+        }
+    }
+
+    /// 3.6V Lithium Iron Phosphate (LiFePO4) cell.
+    ///
+    /// Charged at 0.5C up to 3.6V with C/10 pre-charge and C/20 termination, with the same lower limits as [`li_ion_4v2`](#method.li_ion_4v2).
+    /// Fast charge starts at 2.8V and recharge at 100mV below VREG.
+    pub const fn lifepo4_3v6(capacity_mah: u16) -> Self {
+        Self {
+            chemistry: Chemistry::LithiumIronPhosphate,
+            max_charge_voltage_mv: 3600,
+            ..Self::li_ion_4v2(capacity_mah)
+        }
+    }
+
+    /// Compute the charge registers for this cell
+    pub fn registers(&self) -> Result<ProfileRegisters, ProfileError> {
+        if self.capacity_mah == 0 {
+            return Err(ProfileError::ZeroCapacity);
+        }
+        if self.min_charge_temperature_c > CHARGE_TEMPERATURE_MIN_CELSIUS
+            || self.max_charge_temperature_c < CHARGE_TEMPERATURE_MAX_CELSIUS
+        {
+            return Err(ProfileError::TemperatureWindow);
+        }
+        if self.max_charge_voltage_mv < VREG_MIN_MILLIVOLTS {
+            return Err(ProfileError::ChargeVoltageTooLow);
+        }
+
+        let max_charge_current =
+            u32::from(self.capacity_mah) * u32::from(self.max_charge_rate_percent) / 100;
+        let force_20pct = max_charge_current < u32::from(ICHG_MIN_MILLIAMPS);
+        let ichg = if force_20pct {
+            max_charge_current * 5
+        } else {
+            max_charge_current.min(u32::from(ICHG_MAX_MILLIAMPS))
+        };
+        if ichg < u32::from(ICHG_MIN_MILLIAMPS) {
+            return Err(ProfileError::ChargeCurrentTooLow);
+        }
+        let mut charge_current_control = ChargeCurrentControl::from(0);
+        charge_current_control.set_charge_current_milliamps(ichg as u16);
+        if force_20pct {
+            charge_current_control |= ChargeCurrentControl::FORCE_20PCT;
+        }
+        let charge_current = charge_current_control.effective_charge_current_milliamps();
+
+        let iprechg = if force_20pct {
+            u32::from(self.precharge_current_ma) * 2
+        } else {
+            u32::from(self.precharge_current_ma)
+        };
+        if iprechg < u32::from(IPRECHG_ITERM_MIN_MILLIAMPS) {
+            return Err(ProfileError::PrechargeCurrentTooLow);
+        }
+        if self.termination_current_ma > IPRECHG_ITERM_MAX_MILLIAMPS {
+            return Err(ProfileError::TerminationCurrentTooHigh);
+        }
+        let iterm = self
+            .termination_current_ma
+            .max(IPRECHG_ITERM_MIN_MILLIAMPS)
+            .div_ceil(IPRECHG_ITERM_STEP_MILLIAMPS)
+            * IPRECHG_ITERM_STEP_MILLIAMPS;
+        if iterm >= charge_current {
+            return Err(ProfileError::TerminationCurrentTooHigh);
+        }
+        let mut pre_charge_termination_current_control =
+            PreChargeTerminationCurrentControl::from(0);
+        pre_charge_termination_current_control
+            .set_precharge_current_milliamps(iprechg.min(u32::from(u16::MAX)) as u16);
+        pre_charge_termination_current_control.set_termination_current_milliamps(iterm);
+
+        let mut charge_voltage_control = ChargeVoltageControl::from(0);
+        charge_voltage_control.set_charge_voltage_millivolts(self.max_charge_voltage_mv);
+        charge_voltage_control.set_battery_low_voltage(match self.chemistry {
+            Chemistry::LithiumIon => BatteryLowVoltage::Millivolts3000,
+            Chemistry::LithiumIronPhosphate => BatteryLowVoltage::Millivolts2800,
+        });
+        charge_voltage_control.set_recharge_threshold(RechargeThreshold::Millivolts100);
+
+        // Allow 1.5x the time a constant current charge from empty would take
+        let hours = (u32::from(self.capacity_mah) * 3).div_ceil(u32::from(charge_current) * 2);
+        let safety_timer = [
+            SafetyTimer::Hours5,
+            SafetyTimer::Hours8,
+            SafetyTimer::Hours12,
+        ]
+        .iter()
+        .copied()
+        .find(|timer| u32::from(timer.hours()) >= hours)
+        .unwrap_or(SafetyTimer::Hours20);
+
+        Ok(ProfileRegisters {
+            charge_current_control,
+            pre_charge_termination_current_control,
+            charge_voltage_control,
+            safety_timer,
+        })
+    }
+}
+
+impl ChargerState {
+    /// Apply computed profile registers to the chip state and stage them. Does NOT do an I2C call;
+    /// use [`commit`](struct.ChargerState.html#method.commit) or [`write_all`](struct.ChargerState.html#method.write_all) to write them.
+    ///
+    /// The other fields of [`ChargeTerminationTimerControl`](struct.ChargeTerminationTimerControl.html) are left untouched.
+    pub fn apply_profile(&mut self, registers: &ProfileRegisters) {
+        self.stage_charge_current_control(registers.charge_current_control);
+        self.stage_pre_charge_termination_current_control(
+            registers.pre_charge_termination_current_control,
+        );
+        self.stage_charge_voltage_control(registers.charge_voltage_control);
+        let mut charge_termination_timer_control = self.get_charge_termination_timer_control();
+        charge_termination_timer_control.set_safety_timer(registers.safety_timer);
+        self.stage_charge_termination_timer_control(charge_termination_timer_control);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(capacity_mah: u16) -> BatteryProfile {
+        BatteryProfile {
+            chemistry: Chemistry::LithiumIon,
+            capacity_mah,
+            max_charge_voltage_mv: 4200,
+            max_charge_rate_percent: 100,
+            precharge_current_ma: 256,
+            termination_current_ma: 128,
+            min_charge_temperature_c: CHARGE_TEMPERATURE_MIN_CELSIUS,
+            max_charge_temperature_c: CHARGE_TEMPERATURE_MAX_CELSIUS,
+        }
+    }
+
+    #[test]
+    fn charge_voltage_and_current_round_down() {
+        let registers = BatteryProfile {
+            max_charge_voltage_mv: 4350,
+            ..profile(2000)
+        }
+        .registers()
+        .unwrap();
+        assert_eq!(
+            registers.charge_voltage_control.charge_voltage_millivolts(),
+            4336
+        );
+        assert_eq!(
+            registers
+                .charge_current_control
+                .effective_charge_current_milliamps(),
+            1984
+        );
+    }
+
+    #[test]
+    fn termination_current_rounds_up() {
+        let registers = BatteryProfile {
+            termination_current_ma: 129,
+            ..profile(2000)
+        }
+        .registers()
+        .unwrap();
+        assert_eq!(
+            registers
+                .pre_charge_termination_current_control
+                .termination_current_milliamps(),
+            256
+        );
+    }
+
+    #[test]
+    fn small_charge_current_uses_force_20pct() {
+        let registers = profile(300).registers().unwrap();
+        assert!(registers
+            .charge_current_control
+            .contains(ChargeCurrentControl::FORCE_20PCT));
+        assert!(
+            registers
+                .charge_current_control
+                .effective_charge_current_milliamps()
+                <= 300
+        );
+    }
+
+    #[test]
+    fn presets_support_small_cells() {
+        for capacity_mah in 282..=5000 {
+            for preset in [
+                BatteryProfile::li_ion_4v2,
+                BatteryProfile::li_ion_4v35,
+                BatteryProfile::lifepo4_3v6,
+            ] {
+                assert!(
+                    preset(capacity_mah).registers().is_ok(),
+                    "{}mAh",
+                    capacity_mah
+                );
+            }
+        }
+        assert_eq!(
+            BatteryProfile::li_ion_4v2(281).registers(),
+            Err(ProfileError::TerminationCurrentTooHigh)
+        );
+    }
+
+    #[test]
+    fn termination_current_above_range_is_rejected() {
+        for termination_current_ma in [2049, 65409, u16::MAX] {
+            let profile = BatteryProfile {
+                termination_current_ma,
+                ..profile(u16::MAX)
+            };
+            assert_eq!(
+                profile.registers(),
+                Err(ProfileError::TerminationCurrentTooHigh)
+            );
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! serde support for registers and decoded field values.
+//!
+//! Human-readable formats get a map from field name to decoded value, i.e. `{"EN_HIZ": false, "VINDPM": 4360, "IINLIM": 1500}`,
+//! with voltages in mV and currents in mA. Bits that are not part of any field are kept under `RESERVED` so nothing is lost.
+//! Fields left out when deserializing keep their power-on defaults, but every register of a chip state must be present.
+//!
+//! Compact formats get the raw register value as a single `u8`.
+
+use crate::field::{Encoding, Field, Value, FIELDS};
+use core::convert::TryFrom;
+use core::fmt;
+use serde::de::{self, DeserializeSeed, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Key for the bits of a register that are not part of any field
+const RESERVED: &str = "RESERVED";
+
+/// Bits of a register that are not part of any field
+fn reserved_mask(register: u8) -> u8 {
+    !fields(register).fold(0, |mask, field| mask | field.mask())
+}
+
+fn fields(register: u8) -> impl Iterator<Item = &'static Field> {
+    FIELDS
+        .iter()
+        .filter(move |field| field.register == register)
+}
+
+/// Flags as `bool`, quantities as `u16`, names as strings and raw bits as `u8`
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Value::Flag(flag) => serializer.serialize_bool(flag),
+            Value::Quantity(value, _) => serializer.serialize_u16(value),
+            Value::Named(name) => serializer.serialize_str(name),
+            Value::Raw(raw) => serializer.serialize_u8(raw),
+        }
+    }
+}
+
+pub(crate) fn serialize_register<S: Serializer>(
+    serializer: S,
+    register: u8,
+    bits: u8,
+) -> Result<S::Ok, S::Error> {
+    if !serializer.is_human_readable() {
+        return serializer.serialize_u8(bits);
+    }
+    let reserved = reserved_mask(register);
+    let mut map = serializer.serialize_map(None)?;
+    for field in fields(register) {
+        map.serialize_entry(field.name, &field.decode(bits))?;
+    }
+    if reserved != 0 {
+        map.serialize_entry(RESERVED, &(bits & reserved))?;
+    }
+    map.end()
+}
+
+pub(crate) fn deserialize_register<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    register: u8,
+    default: u8,
+) -> Result<u8, D::Error> {
+    if !deserializer.is_human_readable() {
+        return u8::deserialize(deserializer);
+    }
+    deserializer.deserialize_map(RegisterVisitor { register, default })
+}
+
+struct RegisterVisitor {
+    register: u8,
+    default: u8,
+}
+
+impl<'de> Visitor<'de> for RegisterVisitor {
+    type Value = u8;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of register fields")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<u8, A::Error> {
+        let mut bits = self.default;
+        while let Some(key) = map.next_key_seed(KeySeed {
+            register: self.register,
+        })? {
+            let (mask, value) = match key {
+                Some(field) => (
+                    field.mask(),
+                    map.next_value_seed(ValueSeed { field })? << field.shift,
+                ),
+                None => {
+                    let mask = reserved_mask(self.register);
+                    let value = map.next_value::<u8>()?;
+                    if value & !mask != 0 {
+                        return Err(de::Error::custom(format_args!(
+                            "{} has bits outside of {:#010b}",
+                            RESERVED, mask
+                        )));
+                    }
+                    (mask, value)
+                }
+            };
+            bits = (bits & !mask) | value;
+        }
+        Ok(bits)
+    }
+}
+
+/// Deserializes a field name into the field, or `None` for [`RESERVED`]
+struct KeySeed {
+    register: u8,
+}
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = Option<&'static Field>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeySeed {
+    type Value = Option<&'static Field>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a field name")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+        if name == RESERVED {
+            return Ok(None);
+        }
+        fields(self.register)
+            .find(|field| field.name == name)
+            .map(Some)
+            .ok_or_else(|| E::custom(format_args!("unknown field `{}`", name)))
+    }
+}
+
+/// Deserializes a decoded value into the raw bits of a field
+struct ValueSeed {
+    field: &'static Field,
+}
+
+impl ValueSeed {
+    fn encode<E: de::Error>(&self, value: Value) -> Result<u8, E> {
+        self.field.encode(value).ok_or_else(|| {
+            E::custom(format_args!(
+                "{} is not a valid setting for {}",
+                value, self.field.name
+            ))
+        })
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for ValueSeed {
+    type Value = u8;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<u8, D::Error> {
+        match self.field.encoding {
+            Encoding::Flag => deserializer.deserialize_bool(self),
+            Encoding::Linear { .. } | Encoding::Table { .. } => deserializer.deserialize_u16(self),
+            Encoding::Named(_) => deserializer.deserialize_str(self),
+            Encoding::Raw => deserializer.deserialize_u8(self),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for ValueSeed {
+    type Value = u8;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.field.encoding {
+            Encoding::Flag => f.write_str("a boolean"),
+            Encoding::Linear { unit, .. } | Encoding::Table { unit, .. } => {
+                write!(f, "a whole number of {}", unit)
+            }
+            Encoding::Named(_) => f.write_str("a setting name"),
+            Encoding::Raw => f.write_str("raw bits"),
+        }
+    }
+
+    fn visit_bool<E: de::Error>(self, flag: bool) -> Result<u8, E> {
+        self.encode(Value::Flag(flag))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<u8, E> {
+        match self.field.encoding {
+            Encoding::Linear { unit, .. } | Encoding::Table { unit, .. } => {
+                let value = u16::try_from(value)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))?;
+                self.encode(Value::Quantity(value, unit))
+            }
+            Encoding::Raw => {
+                let raw = u8::try_from(value)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))?;
+                self.encode(Value::Raw(raw))
+            }
+            _ => Err(E::invalid_type(de::Unexpected::Unsigned(value), &self)),
+        }
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<u8, E> {
+        match u64::try_from(value) {
+            Ok(value) => self.visit_u64(value),
+            Err(_) => Err(E::invalid_value(de::Unexpected::Signed(value), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<u8, E> {
+        match self.field.encoding {
+            Encoding::Named(names) => names
+                .iter()
+                .position(|n| *n == name)
+                .map(|raw| raw as u8)
+                .ok_or_else(|| E::invalid_value(de::Unexpected::Str(name), &self)),
+            _ => Err(E::invalid_type(de::Unexpected::Str(name), &self)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ChargerState, InputSourceControl};
+
+    #[test]
+    fn every_register_value_round_trips() {
+        for bits in 0..=u8::MAX {
+            let state = ChargerState::from_registers([bits; 11]);
+
+            let json = serde_json::to_string(&state).unwrap();
+            let from_json: ChargerState = serde_json::from_str(&json).unwrap();
+            assert_eq!(from_json.registers(), state.registers(), "{}", json);
+
+            let bytes = postcard::to_allocvec(&state).unwrap();
+            let from_postcard: ChargerState = postcard::from_bytes(&bytes).unwrap();
+            assert_eq!(from_postcard.registers(), state.registers());
+        }
+    }
+
+    #[test]
+    fn negative_quantity_is_rejected() {
+        let error = serde_json::from_str::<InputSourceControl>(r#"{ "VINDPM": -80 }"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("invalid value: integer `-80`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn missing_register_is_rejected() {
+        let mut json = serde_json::to_value(ChargerState::from_registers([0; 11])).unwrap();
+        json.as_object_mut().unwrap().remove("fault");
+        assert!(serde_json::from_value::<ChargerState>(json).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! Ship mode, where the BATFET is turned off to disconnect the battery.
+//!
+//! Writing 1 to [`MiscOperationControl::BATFET_DISABLE`](../struct.MiscOperationControl.html#associatedconstant.BATFET_DISABLE) disconnects the battery, so the system can only run from the input source.
+//! If the I2C watchdog expires, the registers are reset and the battery is reconnected, so the watchdog should usually be disabled first.
+
+use crate::control::Watchdog;
+use crate::{ChargerState, Error, MiscOperationControl};
+use hal::blocking::i2c::{Write, WriteRead};
+
+/// Options for [`ChargerState::enter_ship_mode`](../struct.ChargerState.html#method.enter_ship_mode)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShipModeOptions {
+    /// Disable the I2C watchdog first so that a watchdog reset does not reconnect the battery
+    pub disable_watchdog: bool,
+    /// Disconnect the battery even if there is no good input source, which will brown out the system
+    pub force: bool,
+}
+
+impl ChargerState {
+    /// Disconnect the battery by turning off the BATFET.
+    ///
+    /// [`SystemStatus::PG_STAT`](struct.SystemStatus.html#associatedconstant.PG_STAT) is read first.
+    /// If the input source is not good, [`Error::NoInputPower`](enum.Error.html#variant.NoInputPower) is returned and nothing is written unless [`ShipModeOptions::force`](ship/struct.ShipModeOptions.html#structfield.force) is set.
+    pub fn enter_ship_mode<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        options: ShipModeOptions,
+    ) -> Result<(), Error<E>> {
+        self.read_system_status(i2c).map_err(Error::I2c)?;
+        if !self.get_system_status().power_good() && !options.force {
+            return Err(Error::NoInputPower);
+        }
+        if options.disable_watchdog {
+            self.write_watchdog(i2c, Watchdog::Disabled)
+                .map_err(Error::I2c)?;
+        }
+        self.write_batfet_disable(i2c, true).map_err(Error::I2c)
+    }
+
+    /// Reconnect the battery by turning the BATFET back on
+    pub fn exit_ship_mode<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+    ) -> Result<(), E> {
+        self.write_batfet_disable(i2c, false)
+    }
+
+    fn write_batfet_disable<E, I2C: Write<Error = E> + WriteRead<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        disable: bool,
+    ) -> Result<(), E> {
+        self.read_misc_operation_control(i2c)?;
+        let misc_operation_control = self.get_misc_operation_control()
+            & !(MiscOperationControl::DPDM_EN | MiscOperationControl::BATFET_DISABLE);
+        self.write_misc_operation_control(
+            i2c,
+            if disable {
+                misc_operation_control | MiscOperationControl::BATFET_DISABLE
+            } else {
+                misc_operation_control
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{ScriptedBus, SimulatedCharger};
+    use crate::NUM_REGISTERS;
+
+    fn bus(system_status: u8) -> ScriptedBus<'static> {
+        let mut sim = SimulatedCharger::new();
+        sim.set_register(0x08, system_status);
+        ScriptedBus::new(sim)
+    }
+
+    #[test]
+    fn enter_disables_watchdog_then_batfet() {
+        let mut i2c = bus(0b0000_0100);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        let options = ShipModeOptions {
+            disable_watchdog: true,
+            force: false,
+        };
+        state.enter_ship_mode(&mut i2c, options).unwrap();
+        assert_eq!(i2c.writes, [[0x05, 0x8A], [0x07, 0x6B]]);
+    }
+
+    #[test]
+    fn enter_can_keep_watchdog() {
+        let mut i2c = bus(0b0000_0100);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        state
+            .enter_ship_mode(&mut i2c, ShipModeOptions::default())
+            .unwrap();
+        assert_eq!(i2c.writes, [[0x07, 0x6B]]);
+    }
+
+    #[test]
+    fn enter_without_input_power_needs_force() {
+        let mut i2c = bus(0);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        assert_eq!(
+            state.enter_ship_mode(&mut i2c, ShipModeOptions::default()),
+            Err(Error::NoInputPower)
+        );
+        assert!(i2c.writes.is_empty());
+
+        let options = ShipModeOptions {
+            disable_watchdog: false,
+            force: true,
+        };
+        state.enter_ship_mode(&mut i2c, options).unwrap();
+        assert_eq!(i2c.writes, [[0x07, 0x6B]]);
+    }
+
+    #[test]
+    fn exit_clears_batfet_disable_and_dpdm_en() {
+        let mut i2c = bus(0);
+        i2c.sim.set_register(0x07, 0b1110_1011);
+        let mut state = ChargerState::from_registers([0; NUM_REGISTERS]);
+        state.exit_ship_mode(&mut i2c).unwrap();
+        assert_eq!(i2c.writes, [[0x07, 0x4B]]);
+    }
+}
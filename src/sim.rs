@@ -0,0 +1,192 @@
+//! Simulated BQ24195 on an I2C bus, for exercising code without hardware.
+//!
+//! [`SimulatedCharger`](struct.SimulatedCharger.html) implements the blocking I2C traits and behaves like the register file of the chip:
+//! the register address auto-increments, the status registers (0x08 - 0x0A) ignore writes, and
+//! [`PowerOnConfiguration::REGISTER_RESET`](../struct.PowerOnConfiguration.html#associatedconstant.REGISTER_RESET) and
+//! [`PowerOnConfiguration::I2C_WATCHDOG_TIMER_RESET`](../struct.PowerOnConfiguration.html#associatedconstant.I2C_WATCHDOG_TIMER_RESET) clear themselves.
+//! [`MiscOperationControl::DPDM_EN`](../struct.MiscOperationControl.html#associatedconstant.DPDM_EN) also clears itself, as if D+/D- detection finished immediately without changing its result,
+//! unless [`hold_detection`](struct.SimulatedCharger.html#method.hold_detection) is used.
+//! Nothing else is simulated, so status and faults are set with [`set_register`](struct.SimulatedCharger.html#method.set_register).
+//!
+//! ```
+//! use bq24195_i2c::sim::SimulatedCharger;
+//! use bq24195_i2c::{ChargeStatus, ChargerState, InputCurrentLimit};
+//!
+//! let mut sim = SimulatedCharger::new();
+//! sim.set_register(0x08, 0b1010_0100);
+//!
+//! let mut state = ChargerState::try_new(&mut sim).unwrap();
+//! assert_eq!(state.get_system_status().charge_status(), ChargeStatus::FastCharging);
+//!
+//! state.write_input_current_limit(&mut sim, InputCurrentLimit::Milliamps1500).unwrap();
+//! assert_eq!(sim.registers()[0x00], 0x35);
+//! ```
+
+use crate::config::ChargerConfig;
+use crate::{
+    MiscOperationControl, PowerOnConfiguration, VendorPartRevisionStatus, ADDRESS, LAST_REGISTER,
+    LAST_WRITABLE_REGISTER, NUM_REGISTERS,
+};
+use hal::blocking::i2c::{Write, WriteRead};
+
+/// Error from a [`SimulatedCharger`](struct.SimulatedCharger.html) transaction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SimError {
+    /// The transaction was not addressed to [`ADDRESS`](../constant.ADDRESS.html)
+    Nack(u8),
+    /// The transaction has no register address
+    NoRegister,
+    /// The transaction went past the last register (0x0A)
+    InvalidRegister(u8),
+}
+
+/// Register file of a simulated BQ24195
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimulatedCharger {
+    registers: [u8; NUM_REGISTERS],
+    hold_detection: bool,
+}
+
+impl Default for SimulatedCharger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedCharger {
+    /// Simulated chip with every register at its power-on default, no input source and no faults
+    pub fn new() -> Self {
+        let mut registers = [0u8; NUM_REGISTERS];
+        registers[..=usize::from(LAST_WRITABLE_REGISTER)]
+            .copy_from_slice(&ChargerConfig::new().registers());
+        registers[usize::from(LAST_REGISTER)] = VendorPartRevisionStatus::default().bits();
+        Self {
+            registers,
+            hold_detection: false,
+        }
+    }
+
+    /// Values of all registers, indexed by address
+    pub fn registers(&self) -> [u8; NUM_REGISTERS] {
+        self.registers
+    }
+
+    /// Set a register regardless of whether it is writable over I2C, i.e. to simulate a change in status or a fault.
+    ///
+    /// # Panics
+    ///
+    /// If the address is past the last register (0x0A).
+    pub fn set_register(&mut self, address: u8, value: u8) {
+        self.registers[usize::from(address)] = value;
+    }
+
+    /// Keep [`MiscOperationControl::DPDM_EN`](../struct.MiscOperationControl.html#associatedconstant.DPDM_EN) as written instead of clearing it, as if D+/D- detection never finished
+    pub fn hold_detection(&mut self, hold: bool) {
+        self.hold_detection = hold;
+    }
+
+    /// Range of registers covered by a transaction of `len` bytes starting at `start`
+    fn range(start: u8, len: usize) -> Result<core::ops::Range<usize>, SimError> {
+        let start = usize::from(start);
+        let end = start + len;
+        if end > NUM_REGISTERS {
+            return Err(SimError::InvalidRegister(NUM_REGISTERS.max(start) as u8));
+        }
+        Ok(start..end)
+    }
+}
+
+impl Write for SimulatedCharger {
+    type Error = SimError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), SimError> {
+        if address != ADDRESS {
+            return Err(SimError::Nack(address));
+        }
+        let (&start, values) = bytes.split_first().ok_or(SimError::NoRegister)?;
+        for (register, value) in Self::range(start, values.len())?.zip(values) {
+            if register > usize::from(LAST_WRITABLE_REGISTER) {
+                continue;
+            }
+            self.registers[register] = *value;
+            if register == 0x07 && !self.hold_detection {
+                // D+/D- detection finishes immediately, without changing the result registers
+                self.registers[register] &= !MiscOperationControl::DPDM_EN.bits();
+            }
+            if register == 0x01 {
+                let reset = PowerOnConfiguration::REGISTER_RESET.bits();
+                if value & reset != 0 {
+                    self.registers[..=usize::from(LAST_WRITABLE_REGISTER)]
+                        .copy_from_slice(&ChargerConfig::new().registers());
+                }
+                self.registers[register] &=
+                    !(reset | PowerOnConfiguration::I2C_WATCHDOG_TIMER_RESET.bits());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WriteRead for SimulatedCharger {
+    type Error = SimError;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), SimError> {
+        if address != ADDRESS {
+            return Err(SimError::Nack(address));
+        }
+        let start = *bytes.first().ok_or(SimError::NoRegister)?;
+        buffer.copy_from_slice(&self.registers[Self::range(start, buffer.len())?]);
+        Ok(())
+    }
+}
+
+/// Bus for unit tests that passes transactions to a [`SimulatedCharger`](struct.SimulatedCharger.html),
+/// records every write, and then runs a script so the simulated chip can react to it
+#[cfg(test)]
+pub(crate) struct ScriptedBus<'a> {
+    pub sim: SimulatedCharger,
+    pub writes: Vec<Vec<u8>>,
+    script: Box<dyn FnMut(&mut SimulatedCharger) + 'a>,
+}
+
+#[cfg(test)]
+impl<'a> ScriptedBus<'a> {
+    /// Bus that only records writes
+    pub fn new(sim: SimulatedCharger) -> Self {
+        Self::with_script(sim, |_| {})
+    }
+
+    /// Bus that runs `script` after every write
+    pub fn with_script(
+        sim: SimulatedCharger,
+        script: impl FnMut(&mut SimulatedCharger) + 'a,
+    ) -> Self {
+        Self {
+            sim,
+            writes: Vec::new(),
+            script: Box::new(script),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Write for ScriptedBus<'_> {
+    type Error = SimError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), SimError> {
+        self.sim.write(address, bytes)?;
+        self.writes.push(bytes.to_vec());
+        (self.script)(&mut self.sim);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl WriteRead for ScriptedBus<'_> {
+    type Error = SimError;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), SimError> {
+        self.sim.write_read(address, bytes, buffer)
+    }
+}
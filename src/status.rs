@@ -0,0 +1,371 @@
+//! Decoded views of the read-only [`SystemStatus`](../struct.SystemStatus.html) and [`Fault`](../struct.Fault.html) registers.
+//!
+//! For polling, [`ChargerState::read_status`](../struct.ChargerState.html#method.read_status) reads both registers in a single transaction,
+//! and [`ChargerState::status_summary`](../struct.ChargerState.html#method.status_summary) describes them in one line for logging.
+
+use crate::{ChargerState, Fault, SystemStatus};
+use core::fmt;
+use hal::blocking::i2c::WriteRead;
+
+/// Input source type reported in [`SystemStatus::VBUS_STAT[1:0]`](../struct.SystemStatus.html#associatedconstant.VBUS_STAT_1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VbusStatus {
+    /// No input or unknown input source (00)
+    Unknown,
+    /// USB host (01)
+    UsbHost,
+    /// Adapter port (10)
+    AdapterPort,
+    /// OTG boost (11)
+    Otg,
+}
+
+/// Charging phase reported in [`SystemStatus::CHRG_STAT[1:0]`](../struct.SystemStatus.html#associatedconstant.CHRG_STAT_1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChargeStatus {
+    /// Not charging (00)
+    NotCharging,
+    /// Pre-charge (01)
+    PreCharge,
+    /// Fast charging (10)
+    FastCharging,
+    /// Charge termination done (11)
+    ChargeTerminationDone,
+}
+
+/// Charging fault reported in [`Fault::CHRG_FAULT[1:0]`](../struct.Fault.html#associatedconstant.CHRG_FAULT_1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChargeFault {
+    /// Normal (00)
+    Normal,
+    /// Input fault: VBUS OVP or VBAT < VBUS < 3.8V (01)
+    InputFault,
+    /// Thermal shutdown (10)
+    ThermalShutdown,
+    /// Charge safety timer expiration (11)
+    SafetyTimerExpired,
+}
+
+/// Thermistor fault reported in [`Fault::NTC_FAULT[2:0]`](../struct.Fault.html#associatedconstant.NTC_FAULT_2)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NtcFault {
+    /// Normal (000)
+    Normal,
+    /// Cold (101)
+    Cold,
+    /// Hot (110)
+    Hot,
+    /// Any other value, which the datasheet does not define
+    Other(u8),
+}
+
+impl VbusStatus {
+    /// Name used by `Display`, i.e. `usb-host`
+    const fn name(self) -> &'static str {
+        match self {
+            VbusStatus::Unknown => "unknown",
+            VbusStatus::UsbHost => "usb-host",
+            VbusStatus::AdapterPort => "adapter-port",
+            VbusStatus::Otg => "otg",
+        }
+    }
+}
+
+impl ChargeStatus {
+    /// Name used by `Display`, i.e. `fast-charging`
+    const fn name(self) -> &'static str {
+        match self {
+            ChargeStatus::NotCharging => "not-charging",
+            ChargeStatus::PreCharge => "pre-charge",
+            ChargeStatus::FastCharging => "fast-charging",
+            ChargeStatus::ChargeTerminationDone => "done",
+        }
+    }
+}
+
+impl ChargeFault {
+    /// Name used by `Display`, i.e. `thermal-shutdown`
+    const fn name(self) -> &'static str {
+        match self {
+            ChargeFault::Normal => "normal",
+            ChargeFault::InputFault => "input",
+            ChargeFault::ThermalShutdown => "thermal-shutdown",
+            ChargeFault::SafetyTimerExpired => "safety-timer",
+        }
+    }
+}
+
+impl NtcFault {
+    /// Name used by `Display`, i.e. `cold`. [`Other`](#variant.Other) is followed by its raw bits.
+    const fn name(self) -> &'static str {
+        match self {
+            NtcFault::Normal => "normal",
+            NtcFault::Cold => "cold",
+            NtcFault::Hot => "hot",
+            NtcFault::Other(_) => "other",
+        }
+    }
+}
+
+/// `Display` and `defmt::Format` that write the name of each variant
+macro_rules! named {
+    ($($type: ty),*) => {
+        $(
+            impl fmt::Display for $type {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str(self.name())
+                }
+            }
+
+            #[cfg(feature = "defmt")]
+            impl defmt::Format for $type {
+                fn format(&self, f: defmt::Formatter<'_>) {
+                    defmt::write!(f, "{=str}", self.name())
+                }
+            }
+        )*
+    };
+}
+
+named!(VbusStatus, ChargeStatus, ChargeFault);
+
+impl fmt::Display for NtcFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
+        if let NtcFault::Other(bits) = self {
+            write!(f, " ({:#05b})", bits)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for NtcFault {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=str}", self.name());
+        if let NtcFault::Other(bits) = self {
+            defmt::write!(f, " ({=u8:#05b})", bits);
+        }
+    }
+}
+
+/// One-line description of [`SystemStatus`](../struct.SystemStatus.html) and [`Fault`](../struct.Fault.html),
+/// i.e. `adapter-port, fast-charging, power good, DPM`. Only active conditions and faults are listed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatusSummary {
+    /// System status
+    pub status: SystemStatus,
+    /// Fault
+    pub fault: Fault,
+}
+
+/// Something listed in a [`StatusSummary`] after the input source and charging phase
+enum Condition {
+    /// A fixed description, i.e. `power good`
+    Text(&'static str),
+    /// A charge fault other than `normal`
+    ChargeFault(ChargeFault),
+    /// An NTC fault other than `normal`
+    NtcFault(NtcFault),
+}
+
+impl StatusSummary {
+    /// Active conditions and faults, in the order they are listed
+    fn conditions(&self) -> [Option<Condition>; 9] {
+        let (status, fault) = (self.status, self.fault);
+        let text = |active: bool, text| {
+            if active {
+                Some(Condition::Text(text))
+            } else {
+                None
+            }
+        };
+        [
+            Some(Condition::Text(if status.power_good() {
+                "power good"
+            } else {
+                "no power"
+            })),
+            text(status.dpm(), "DPM"),
+            text(status.thermal_regulation(), "thermal regulation"),
+            text(status.vsys_regulation(), "VSYSMIN regulation"),
+            text(fault.watchdog_fault(), "watchdog fault"),
+            text(fault.boost_fault(), "boost fault"),
+            Some(Condition::ChargeFault(fault.charge_fault()))
+                .filter(|_| fault.charge_fault() != ChargeFault::Normal),
+            text(fault.battery_fault(), "battery fault"),
+            Some(Condition::NtcFault(fault.ntc_fault()))
+                .filter(|_| fault.ntc_fault() != NtcFault::Normal),
+        ]
+    }
+}
+
+impl fmt::Display for StatusSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, {}",
+            self.status.vbus_status(),
+            self.status.charge_status()
+        )?;
+        for condition in self.conditions().iter().flatten() {
+            match condition {
+                Condition::Text(text) => write!(f, ", {}", text)?,
+                Condition::ChargeFault(fault) => write!(f, ", charge fault: {}", fault)?,
+                Condition::NtcFault(fault) => write!(f, ", NTC fault: {}", fault)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Same as `Display`
+#[cfg(feature = "defmt")]
+impl defmt::Format for StatusSummary {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "{}, {}",
+            self.status.vbus_status(),
+            self.status.charge_status()
+        );
+        for condition in self.conditions().iter().flatten() {
+            match condition {
+                Condition::Text(text) => defmt::write!(f, ", {=str}", text),
+                Condition::ChargeFault(fault) => defmt::write!(f, ", charge fault: {}", fault),
+                Condition::NtcFault(fault) => defmt::write!(f, ", NTC fault: {}", fault),
+            }
+        }
+    }
+}
+
+impl SystemStatus {
+    /// Input source type
+    pub fn vbus_status(&self) -> VbusStatus {
+        match self.bits() >> 6 {
+            0b00 => VbusStatus::Unknown,
+            0b01 => VbusStatus::UsbHost,
+            0b10 => VbusStatus::AdapterPort,
+            _ => VbusStatus::Otg,
+        }
+    }
+
+    /// Charging phase
+    pub fn charge_status(&self) -> ChargeStatus {
+        match (self.bits() >> 4) & 0b11 {
+            0b00 => ChargeStatus::NotCharging,
+            0b01 => ChargeStatus::PreCharge,
+            0b10 => ChargeStatus::FastCharging,
+            _ => ChargeStatus::ChargeTerminationDone,
+        }
+    }
+
+    /// True if the chip is in input voltage or input current regulation (VINDPM / IINDPM)
+    pub fn dpm(&self) -> bool {
+        self.contains(Self::DPM_STAT)
+    }
+
+    /// True if the input source is good
+    pub fn power_good(&self) -> bool {
+        self.contains(Self::PG_STAT)
+    }
+
+    /// True if the chip is in thermal regulation
+    pub fn thermal_regulation(&self) -> bool {
+        self.contains(Self::THERM_STAT)
+    }
+
+    /// True if the battery voltage is below VSYSMIN
+    pub fn vsys_regulation(&self) -> bool {
+        self.contains(Self::VSYS_STAT)
+    }
+}
+
+impl Fault {
+    /// True if the I2C watchdog timer expired, meaning the chip is in default mode
+    pub fn watchdog_fault(&self) -> bool {
+        self.contains(Self::WATCHDOG_FAULT)
+    }
+
+    /// True if OTG boost was overloaded, VBUS went over-voltage, or the battery was too low in boost mode
+    pub fn boost_fault(&self) -> bool {
+        self.contains(Self::BOOST_FAULT)
+    }
+
+    /// Charging fault
+    pub fn charge_fault(&self) -> ChargeFault {
+        match (self.bits() >> 4) & 0b11 {
+            0b00 => ChargeFault::Normal,
+            0b01 => ChargeFault::InputFault,
+            0b10 => ChargeFault::ThermalShutdown,
+            _ => ChargeFault::SafetyTimerExpired,
+        }
+    }
+
+    /// True if battery over-voltage protection (BATOVP) tripped
+    pub fn battery_fault(&self) -> bool {
+        self.contains(Self::BAT_FAULT)
+    }
+
+    /// Thermistor fault
+    pub fn ntc_fault(&self) -> NtcFault {
+        match self.bits() & 0b111 {
+            0b000 => NtcFault::Normal,
+            0b101 => NtcFault::Cold,
+            0b110 => NtcFault::Hot,
+            other => NtcFault::Other(other),
+        }
+    }
+}
+
+impl ChargerState {
+    /// Read [`SystemStatus`](struct.SystemStatus.html) and [`Fault`](struct.Fault.html) (0x08 - 0x09) in a single transaction, updating the chip state.
+    ///
+    /// [`Fault`](struct.Fault.html) is latched: the first read after a fault reports it, and the next read reports the current state.
+    /// Each call reads it exactly once, so a fault is reported by the call that follows it even if it has since cleared.
+    ///
+    /// If an error occurs, the chip state remains the same.
+    pub fn read_status<E, I2C: WriteRead<Error = E>>(&mut self, i2c: &mut I2C) -> Result<(), E> {
+        self.read_range(i2c, 0x08..=0x09)
+    }
+
+    /// One-line description of the status and fault registers in the chip state. Does NOT do an I2C call.
+    pub fn status_summary(&self) -> StatusSummary {
+        StatusSummary {
+            status: self.get_system_status(),
+            fault: self.get_fault(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(status: u8, fault: u8) -> String {
+        StatusSummary {
+            status: SystemStatus::from(status),
+            fault: Fault::from(fault),
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn lists_active_conditions_in_order() {
+        assert_eq!(
+            summary(0b1010_0100, 0),
+            "adapter-port, fast-charging, power good"
+        );
+        assert_eq!(summary(0, 0), "unknown, not-charging, no power");
+        assert_eq!(
+            summary(0b0101_1111, 0b1111_1111),
+            "usb-host, pre-charge, power good, DPM, thermal regulation, VSYSMIN regulation, \
+             watchdog fault, boost fault, charge fault: safety-timer, battery fault, NTC fault: other (0b111)"
+        );
+    }
+}
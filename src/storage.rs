@@ -0,0 +1,187 @@
+//! CRC protected byte image of a [`ChargerConfig`](../config/struct.ChargerConfig.html) for storing in flash.
+//!
+//! Version 1 of the blob is 12 bytes:
+//!
+//! | Byte | Content |
+//! |------|---------|
+//! | 0 | Version, 1 |
+//! | 1 | [`VendorPartRevisionStatus`](../struct.VendorPartRevisionStatus.html) (0x0A) of the chip it was made for |
+//! | 2 - 9 | Writable registers (0x00 - 0x07) |
+//! | 10 - 11 | CRC-16/CCITT-FALSE of bytes 0 - 9, big endian |
+//!
+//! At boot, [`ChargerState::apply_blob`](../struct.ChargerState.html#method.apply_blob) checks the blob against the chip and applies it,
+//! or applies a compiled-in fallback if the blob is corrupted, made for another part, or breaks a datasheet constraint:
+//!
+//! ```
+//! # use embedded_hal::blocking::i2c::{Write, WriteRead};
+//! use bq24195_i2c::config::ChargerConfig;
+//! use bq24195_i2c::storage::BlobError;
+//! use bq24195_i2c::ChargerState;
+//!
+//! static FALLBACK: ChargerConfig = ChargerConfig::new().charge_voltage_millivolts(4112);
+//!
+//! fn boot<E, I2C: Write<Error = E> + WriteRead<Error = E>>(i2c: &mut I2C, flash: &[u8]) -> Result<ChargerState, E> {
+//!     let mut state = ChargerState::try_new(i2c)?;
+//!     if let Some(BlobError::Crc) = state.apply_blob(i2c, flash, &FALLBACK)? {
+//!         // The stored configuration is corrupted, so rewrite it
+//!     }
+//!     Ok(state)
+//! }
+//! ```
+
+use crate::config::ChargerConfig;
+use crate::validate::Violations;
+use crate::{ChargerState, MiscOperationControl, PowerOnConfiguration, VendorPartRevisionStatus};
+use hal::blocking::i2c::Write;
+
+/// Version written by [`ChargerConfig::to_blob`](../config/struct.ChargerConfig.html#method.to_blob)
+pub const VERSION: u8 = 1;
+
+/// Length in bytes of a blob
+pub const BLOB_LEN: usize = 12;
+
+/// Reason a blob was rejected
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BlobError {
+    /// The blob is not [`BLOB_LEN`](constant.BLOB_LEN.html) bytes long
+    Length(usize),
+    /// The CRC does not match, so the blob is corrupted or was never written
+    Crc,
+    /// The version is newer than this library, or 0
+    UnsupportedVersion(u8),
+    /// The blob was made for a chip that reports a different [`VendorPartRevisionStatus`](../struct.VendorPartRevisionStatus.html)
+    PartMismatch {
+        /// Part the blob was made for
+        stored: VendorPartRevisionStatus,
+        /// Part of the chip
+        chip: VendorPartRevisionStatus,
+    },
+    /// [`PowerOnConfiguration::REGISTER_RESET`](../struct.PowerOnConfiguration.html#associatedconstant.REGISTER_RESET) or [`MiscOperationControl::DPDM_EN`](../struct.MiscOperationControl.html#associatedconstant.DPDM_EN) is set.
+    /// These bits clear themselves, so applying the blob would reset the registers or force input detection instead of configuring the chip.
+    SelfClearingBits,
+    /// The configuration breaks datasheet constraints
+    Invalid(Violations),
+}
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF, no reflection
+const fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    let mut i = 0;
+    while i < bytes.len() {
+        crc ^= (bytes[i] as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        i += 1;
+    }
+    crc
+}
+
+impl ChargerConfig {
+    /// Blob of the configuration for a chip that reports `part` in [`VendorPartRevisionStatus`](../struct.VendorPartRevisionStatus.html)
+    pub fn to_blob(&self, part: VendorPartRevisionStatus) -> [u8; BLOB_LEN] {
+        let mut blob = [0u8; BLOB_LEN];
+        blob[0] = VERSION;
+        blob[1] = part.bits();
+        blob[2..10].copy_from_slice(&self.registers());
+        let crc = crc16(&blob[..10]);
+        blob[10..].copy_from_slice(&crc.to_be_bytes());
+        blob
+    }
+
+    /// Configuration stored in a blob, if it is intact, made for a chip that reports `part`, has no self-clearing bits set, and passes [`validate`](#method.validate)
+    ///
+    /// ```
+    /// use bq24195_i2c::config::ChargerConfig;
+    /// use bq24195_i2c::storage::BlobError;
+    /// use bq24195_i2c::VendorPartRevisionStatus;
+    ///
+    /// let part = VendorPartRevisionStatus::default();
+    /// let config = ChargerConfig::new().charge_current_milliamps(1024);
+    /// let mut blob = config.to_blob(part);
+    /// assert_eq!(ChargerConfig::from_blob(&blob, part), Ok(config));
+    ///
+    /// blob[4] ^= 0x80;
+    /// assert_eq!(ChargerConfig::from_blob(&blob, part), Err(BlobError::Crc));
+    /// ```
+    pub fn from_blob(blob: &[u8], part: VendorPartRevisionStatus) -> Result<Self, BlobError> {
+        if blob.len() != BLOB_LEN {
+            return Err(BlobError::Length(blob.len()));
+        }
+        if crc16(&blob[..10]).to_be_bytes() != blob[10..] {
+            return Err(BlobError::Crc);
+        }
+        if blob[0] != VERSION {
+            return Err(BlobError::UnsupportedVersion(blob[0]));
+        }
+        let stored = VendorPartRevisionStatus::from(blob[1]);
+        if stored != part {
+            return Err(BlobError::PartMismatch { stored, chip: part });
+        }
+        let mut registers = [0u8; 8];
+        registers.copy_from_slice(&blob[2..10]);
+        let config = Self::from_registers(registers);
+        if PowerOnConfiguration::from(registers[0x01])
+            .contains(PowerOnConfiguration::REGISTER_RESET)
+            || MiscOperationControl::from(registers[0x07]).contains(MiscOperationControl::DPDM_EN)
+        {
+            return Err(BlobError::SelfClearingBits);
+        }
+        let violations = config.validate();
+        if !violations.is_empty() {
+            return Err(BlobError::Invalid(violations));
+        }
+        Ok(config)
+    }
+}
+
+impl ChargerState {
+    /// Apply the configuration stored in a blob with [`write_config`](#method.write_config), or `fallback` if the blob is rejected.
+    /// The part is taken from [`VendorPartRevisionStatus`](struct.VendorPartRevisionStatus.html) in the chip state, so it must have been read.
+    ///
+    /// Returns the reason the blob was rejected, or `None` if it was applied.
+    ///
+    /// If an error occurs, the chip state remains the same.
+    pub fn apply_blob<E, I2C: Write<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        blob: &[u8],
+        fallback: &ChargerConfig,
+    ) -> Result<Option<BlobError>, E> {
+        match ChargerConfig::from_blob(blob, self.get_vendor_part_revision_status()) {
+            Ok(config) => self.write_config(i2c, &config).map(|()| None),
+            Err(err) => self.write_config(i2c, fallback).map(|()| Some(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn self_clearing_bits_are_rejected() {
+        let part = VendorPartRevisionStatus::default();
+        for (address, bit) in [(0x01, 0x80), (0x07, 0x80)] {
+            let mut registers = ChargerConfig::new().registers();
+            registers[address] |= bit;
+            let blob = ChargerConfig::from_registers(registers).to_blob(part);
+            assert_eq!(
+                ChargerConfig::from_blob(&blob, part),
+                Err(BlobError::SelfClearingBits)
+            );
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Compact, versioned encoding of the chip state for low bandwidth links like LoRa.
+//!
+//! A [`Telemetry`](struct.Telemetry.html) frame is the status, the fault, and the registers that set how the chip charges, behind a version byte.
+//! Version 1 is 7 bytes:
+//!
+//! | Byte | Content |
+//! |------|---------|
+//! | 0 | Version, 1 |
+//! | 1 | [`SystemStatus`](../struct.SystemStatus.html) (0x08) |
+//! | 2 | [`Fault`](../struct.Fault.html) (0x09) |
+//! | 3 | [`InputSourceControl`](../struct.InputSourceControl.html) (0x00) |
+//! | 4 | [`PowerOnConfiguration`](../struct.PowerOnConfiguration.html) (0x01) |
+//! | 5 | [`ChargeCurrentControl`](../struct.ChargeCurrentControl.html) (0x02) |
+//! | 6 | [`ChargeVoltageControl`](../struct.ChargeVoltageControl.html) (0x04) |
+//!
+//! The device encodes with [`ChargerState::telemetry`](../struct.ChargerState.html#method.telemetry) and [`Telemetry::encode`](struct.Telemetry.html#method.encode).
+//! The backend decodes with [`Telemetry::decode`](struct.Telemetry.html#method.decode), which keeps accepting every older version as the format grows.
+//!
+//! ```
+//! use bq24195_i2c::telemetry::Telemetry;
+//! use bq24195_i2c::{ChargeStatus, VbusStatus};
+//!
+//! let frame = [1, 0b1010_0100, 0x00, 0x35, 0x1B, 0x60, 0xB2];
+//! let telemetry = Telemetry::decode(&frame).unwrap();
+//! assert_eq!(telemetry.status.vbus_status(), VbusStatus::AdapterPort);
+//! assert_eq!(telemetry.status.charge_status(), ChargeStatus::FastCharging);
+//! assert_eq!(telemetry.encode(), frame);
+//! ```
+
+use crate::status::StatusSummary;
+use crate::{
+    ChargeCurrentControl, ChargeVoltageControl, ChargerState, Fault, InputSourceControl,
+    PowerOnConfiguration, SystemStatus,
+};
+
+/// Version written by [`Telemetry::encode`](struct.Telemetry.html#method.encode)
+pub const VERSION: u8 = 1;
+
+/// Length in bytes of a frame written by [`Telemetry::encode`](struct.Telemetry.html#method.encode)
+pub const FRAME_LEN: usize = 7;
+
+/// Reason a frame could not be decoded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// The frame is empty, so it has no version
+    Empty,
+    /// The version is newer than this library, or 0
+    UnsupportedVersion(u8),
+    /// The frame is not the length its version requires
+    Length {
+        /// Length of the version
+        expected: usize,
+        /// Length of the frame
+        actual: usize,
+    },
+}
+
+/// Status, fault and charging configuration of the chip, as sent in a frame
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Telemetry {
+    /// System status
+    pub status: SystemStatus,
+    /// Fault
+    pub fault: Fault,
+    /// HIZ, input voltage limit and input current limit
+    pub input_source_control: InputSourceControl,
+    /// Charger mode and minimum system voltage
+    pub power_on_configuration: PowerOnConfiguration,
+    /// Fast charge current
+    pub charge_current_control: ChargeCurrentControl,
+    /// Charge voltage, pre-charge threshold and recharge threshold
+    pub charge_voltage_control: ChargeVoltageControl,
+}
+
+impl Telemetry {
+    /// Encode as a frame of the current [`VERSION`](constant.VERSION.html)
+    pub fn encode(&self) -> [u8; FRAME_LEN] {
+        [
+            VERSION,
+            self.status.bits(),
+            self.fault.bits(),
+            self.input_source_control.bits(),
+            self.power_on_configuration.bits(),
+            self.charge_current_control.bits(),
+            self.charge_voltage_control.bits(),
+        ]
+    }
+
+    /// Decode a frame of any version up to the current [`VERSION`](constant.VERSION.html)
+    pub fn decode(frame: &[u8]) -> Result<Self, DecodeError> {
+        let version = *frame.first().ok_or(DecodeError::Empty)?;
+        let expected = match version {
+            1 => 7,
+            _ => return Err(DecodeError::UnsupportedVersion(version)),
+        };
+        if frame.len() != expected {
+            return Err(DecodeError::Length {
+                expected,
+                actual: frame.len(),
+            });
+        }
+        Ok(Self {
+            status: frame[1].into(),
+            fault: frame[2].into(),
+            input_source_control: frame[3].into(),
+            power_on_configuration: frame[4].into(),
+            charge_current_control: frame[5].into(),
+            charge_voltage_control: frame[6].into(),
+        })
+    }
+
+    /// One-line description of the status and fault
+    pub fn status_summary(&self) -> StatusSummary {
+        StatusSummary {
+            status: self.status,
+            fault: self.fault,
+        }
+    }
+}
+
+impl ChargerState {
+    /// Telemetry for the chip state. Does NOT do an I2C call, so read the status first, i.e. with [`read_status`](#method.read_status).
+    pub fn telemetry(&self) -> Telemetry {
+        Telemetry {
+            status: self.get_system_status(),
+            fault: self.get_fault(),
+            input_source_control: self.get_input_source_control(),
+            power_on_configuration: self.get_power_on_configuration(),
+            charge_current_control: self.get_charge_current_control(),
+            charge_voltage_control: self.get_charge_voltage_control(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_frame_is_rejected() {
+        assert_eq!(Telemetry::decode(&[]), Err(DecodeError::Empty));
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        assert_eq!(
+            Telemetry::decode(&[0, 0, 0, 0, 0, 0, 0]),
+            Err(DecodeError::UnsupportedVersion(0))
+        );
+        assert_eq!(
+            Telemetry::decode(&[VERSION + 1, 0, 0, 0, 0, 0, 0]),
+            Err(DecodeError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn wrong_length_is_rejected() {
+        assert_eq!(
+            Telemetry::decode(&[1, 0, 0]),
+            Err(DecodeError::Length {
+                expected: 7,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            Telemetry::decode(&[1, 0, 0, 0, 0, 0, 0, 0]),
+            Err(DecodeError::Length {
+                expected: 7,
+                actual: 8
+            })
+        );
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let mut registers = [
+            0x35, 0x1B, 0x60, 0x11, 0xB2, 0x9A, 0x03, 0x4B, 0xA4, 0x40, 0x23,
+        ];
+        let telemetry = ChargerState::from_registers(registers).telemetry();
+        let frame = telemetry.encode();
+        assert_eq!(frame, [VERSION, 0xA4, 0x40, 0x35, 0x1B, 0x60, 0xB2]);
+        assert_eq!(Telemetry::decode(&frame), Ok(telemetry));
+
+        // Registers that are not sent do not matter
+        registers[0x03] = 0;
+        registers[0x0A] = 0;
+        assert_eq!(
+            ChargerState::from_registers(registers).telemetry(),
+            telemetry
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Checks for datasheet constraints that span several fields or registers.
+//!
+//! [`ChargerState::validate`](../struct.ChargerState.html#method.validate) checks the writable registers (0x00 - 0x07) of the chip state before they are written.
+//! Constraints that depend on the board or the cell, like the ILIM resistor or the cell's maximum voltage,
+//! are only checked by [`ChargerState::validate_with`](../struct.ChargerState.html#method.validate_with) when given in [`Limits`](struct.Limits.html).
+
+use crate::adaptive::ilim_milliamps;
+use crate::control::VREG_MAX_MILLIVOLTS;
+use crate::{
+    ChargeCurrentControl, ChargeTerminationTimerControl, ChargeVoltageControl, ChargerState,
+    InputSourceControl, MiscOperationControl, PowerOnConfiguration,
+    PreChargeTerminationCurrentControl, ThermalRegulationControl,
+};
+use core::fmt;
+
+/// A broken datasheet constraint
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Violation {
+    /// [`ChargeCurrentControl::FORCE_20PCT`](../struct.ChargeCurrentControl.html#associatedconstant.FORCE_20PCT) is set and the termination current is not below 20% of the fast charge current, so charging will not terminate
+    TerminationNotBelow20Percent,
+    /// The termination current is not below the fast charge current, so charging will not terminate
+    TerminationNotBelowChargeCurrent,
+    /// The pre-charge current is above the fast charge current
+    PrechargeAboveChargeCurrent,
+    /// The input current limit is above the ceiling set by the ILIM pin, which it can only reduce
+    InputCurrentAboveIlim,
+    /// The charge voltage limit is above the cell's maximum charge voltage
+    ChargeVoltageAboveCellMax,
+    /// The charge voltage limit is above 4.4V, the highest the datasheet supports
+    ChargeVoltageOutOfSpec,
+    /// The minimum system voltage is not below the charge voltage limit
+    SystemMinimumNotBelowChargeVoltage,
+    /// A reserved bit does not have the value the datasheet says must be written
+    ReservedBits,
+}
+
+impl Violation {
+    /// Every violation, in the order they are reported
+    pub const ALL: [Violation; 8] = [
+        Violation::TerminationNotBelow20Percent,
+        Violation::TerminationNotBelowChargeCurrent,
+        Violation::PrechargeAboveChargeCurrent,
+        Violation::InputCurrentAboveIlim,
+        Violation::ChargeVoltageAboveCellMax,
+        Violation::ChargeVoltageOutOfSpec,
+        Violation::SystemMinimumNotBelowChargeVoltage,
+        Violation::ReservedBits,
+    ];
+
+    /// Description of the broken constraint
+    pub const fn description(self) -> &'static str {
+        match self {
+            Violation::TerminationNotBelow20Percent => {
+                "FORCE_20PCT is set and the termination current is not below 20% of the fast charge current"
+            }
+            Violation::TerminationNotBelowChargeCurrent => {
+                "the termination current is not below the fast charge current"
+            }
+            Violation::PrechargeAboveChargeCurrent => {
+                "the pre-charge current is above the fast charge current"
+            }
+            Violation::InputCurrentAboveIlim => {
+                "the input current limit is above the ceiling set by the ILIM pin"
+            }
+            Violation::ChargeVoltageAboveCellMax => {
+                "the charge voltage limit is above the cell's maximum charge voltage"
+            }
+            Violation::ChargeVoltageOutOfSpec => "the charge voltage limit is above 4.4V",
+            Violation::SystemMinimumNotBelowChargeVoltage => {
+                "the minimum system voltage is not below the charge voltage limit"
+            }
+            Violation::ReservedBits => "a reserved bit does not have its required value",
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// Set of [`Violation`](enum.Violation.html)s found by validation
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Violations {
+    bits: u16,
+}
+
+impl Violations {
+    /// True if no constraints were broken
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// True if `violation` was found
+    pub const fn contains(&self, violation: Violation) -> bool {
+        self.bits & (1 << violation as u16) != 0
+    }
+
+    /// Iterate over the violations found
+    pub fn iter(&self) -> impl Iterator<Item = Violation> {
+        let violations = *self;
+        Violation::ALL
+            .iter()
+            .copied()
+            .filter(move |violation| violations.contains(*violation))
+    }
+
+    const fn with(self, violation: Violation) -> Self {
+        Self {
+            bits: self.bits | 1 << violation as u16,
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Violations {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "[");
+        for (i, violation) in self.iter().enumerate() {
+            if i != 0 {
+                defmt::write!(f, ", ");
+            }
+            defmt::write!(f, "{}", violation);
+        }
+        defmt::write!(f, "]");
+    }
+}
+
+/// Board and cell limits that the registers alone do not capture
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Limits {
+    /// Resistance of the ILIM pin resistor in ohms
+    pub r_ilim_ohms: Option<u32>,
+    /// Maximum charge voltage of the cell in mV
+    pub max_charge_voltage_mv: Option<u16>,
+}
+
+impl ChargerState {
+    /// Check the writable registers of the chip state against the datasheet constraints. Does NOT do an I2C call.
+    pub fn validate(&self) -> Violations {
+        self.validate_with(&Limits::default())
+    }
+
+    /// Check the writable registers of the chip state against the datasheet constraints and the given board and cell limits. Does NOT do an I2C call.
+    pub fn validate_with(&self, limits: &Limits) -> Violations {
+        validate_registers(
+            &[
+                self.get_input_source_control().into(),
+                self.get_power_on_configuration().into(),
+                self.get_charge_current_control().into(),
+                self.get_pre_charge_termination_current_control().into(),
+                self.get_charge_voltage_control().into(),
+                self.get_charge_termination_timer_control().into(),
+                self.get_thermal_regulation_control().into(),
+                self.get_misc_operation_control().into(),
+            ],
+            limits,
+        )
+    }
+}
+
+/// Check an image of the writable registers (0x00 - 0x07)
+///
+/// This is a `const fn` so that [`ChargerConfig::assert_valid`](../config/struct.ChargerConfig.html#method.assert_valid) can run it at compile time.
+pub(crate) const fn validate_registers(values: &[u8; 8], limits: &Limits) -> Violations {
+    let input_source_control = InputSourceControl::from_bits(values[0x00]);
+    let power_on_configuration = PowerOnConfiguration::from_bits(values[0x01]);
+    let charge_current_control = ChargeCurrentControl::from_bits(values[0x02]);
+    let pre_charge_termination_current_control =
+        PreChargeTerminationCurrentControl::from_bits(values[0x03]);
+    let charge_voltage_control = ChargeVoltageControl::from_bits(values[0x04]);
+    let charge_termination_timer_control = ChargeTerminationTimerControl::from_bits(values[0x05]);
+    let thermal_regulation_control = ThermalRegulationControl::from_bits(values[0x06]);
+    let misc_operation_control = MiscOperationControl::from_bits(values[0x07]);
+
+    let mut violations = Violations { bits: 0 };
+
+    let force_20pct = charge_current_control.contains(ChargeCurrentControl::FORCE_20PCT);
+    let charge_current = charge_current_control.effective_charge_current_milliamps();
+    if pre_charge_termination_current_control.termination_current_milliamps() >= charge_current {
+        violations = violations.with(if force_20pct {
+            Violation::TerminationNotBelow20Percent
+        } else {
+            Violation::TerminationNotBelowChargeCurrent
+        });
+    }
+    let precharge_current = if force_20pct {
+        pre_charge_termination_current_control.precharge_current_milliamps() / 2
+    } else {
+        pre_charge_termination_current_control.precharge_current_milliamps()
+    };
+    if precharge_current > charge_current {
+        violations = violations.with(Violation::PrechargeAboveChargeCurrent);
+    }
+
+    if let Some(r_ilim_ohms) = limits.r_ilim_ohms {
+        if input_source_control.input_current_limit().milliamps() as u32
+            > ilim_milliamps(r_ilim_ohms)
+        {
+            violations = violations.with(Violation::InputCurrentAboveIlim);
+        }
+    }
+
+    let charge_voltage = charge_voltage_control.charge_voltage_millivolts();
+    if let Some(max_charge_voltage_mv) = limits.max_charge_voltage_mv {
+        if charge_voltage > max_charge_voltage_mv {
+            violations = violations.with(Violation::ChargeVoltageAboveCellMax);
+        }
+    }
+    if charge_voltage > VREG_MAX_MILLIVOLTS {
+        violations = violations.with(Violation::ChargeVoltageOutOfSpec);
+    }
+    if power_on_configuration.system_minimum_millivolts() >= charge_voltage {
+        violations = violations.with(Violation::SystemMinimumNotBelowChargeVoltage);
+    }
+
+    let reserved_ok = power_on_configuration.contains(PowerOnConfiguration::RESERVED)
+        && !charge_current_control.contains(ChargeCurrentControl::RESERVED)
+        && !charge_termination_timer_control.contains(ChargeTerminationTimerControl::RESERVED)
+        && thermal_regulation_control.bits() & 0b1111_1100 == 0
+        && misc_operation_control.bits() & 0b0001_1100 == 0b0000_1000;
+    if !reserved_ok {
+        violations = violations.with(Violation::ReservedBits);
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChargerConfig;
+
+    /// Validate the defaults with some registers changed
+    fn violations(changes: &[(usize, u8)], limits: &Limits) -> Vec<Violation> {
+        let mut values = ChargerConfig::new().registers();
+        for (address, value) in changes {
+            values[*address] = *value;
+        }
+        validate_registers(&values, limits).iter().collect()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert_eq!(violations(&[], &Limits::default()), []);
+    }
+
+    #[test]
+    fn termination_and_precharge_against_charge_current() {
+        // 512mA fast charge, 2048mA termination
+        assert_eq!(
+            violations(&[(0x02, 0x00), (0x03, 0x1F)], &Limits::default()),
+            [Violation::TerminationNotBelowChargeCurrent]
+        );
+        // 20% of 512mA fast charge, 256mA pre-charge halved and 256mA termination
+        assert_eq!(
+            violations(&[(0x02, 0x01)], &Limits::default()),
+            [
+                Violation::TerminationNotBelow20Percent,
+                Violation::PrechargeAboveChargeCurrent,
+            ]
+        );
+    }
+
+    #[test]
+    fn board_and_cell_limits() {
+        let limits = Limits {
+            r_ilim_ohms: Some(530),
+            max_charge_voltage_mv: Some(4200),
+        };
+        assert_eq!(
+            violations(&[(0x00, 0x37)], &limits),
+            [
+                Violation::InputCurrentAboveIlim,
+                Violation::ChargeVoltageAboveCellMax,
+            ]
+        );
+    }
+
+    #[test]
+    fn voltages() {
+        assert_eq!(
+            violations(&[(0x04, 0xFE)], &Limits::default()),
+            [Violation::ChargeVoltageOutOfSpec]
+        );
+        // 3.7V minimum system voltage, 3.504V charge voltage
+        assert_eq!(
+            violations(&[(0x01, 0x1F), (0x04, 0x02)], &Limits::default()),
+            [Violation::SystemMinimumNotBelowChargeVoltage]
+        );
+    }
+
+    #[test]
+    fn reserved_bits() {
+        for (address, value) in [
+            (0x01, 0x1B & !0x01),
+            (0x02, 0x62),
+            (0x06, 0x07),
+            (0x07, 0x43),
+        ] {
+            assert_eq!(
+                violations(&[(address, value)], &Limits::default()),
+                [Violation::ReservedBits]
+            );
+        }
+    }
+}
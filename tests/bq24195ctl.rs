@@ -0,0 +1,123 @@
+//! Runs `bq24195ctl` against its simulated charger (`--sim` or `--sim-detecting`), which starts from the power-on defaults on every run.
+
+use std::process::{Command, Output};
+use std::{env, fs, str};
+
+fn bq24195ctl(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_bq24195ctl"))
+        .arg("--sim")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Standard output of a successful run against a simulated charger that is in the middle of D+/D- detection
+fn bq24195ctl_detecting(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_bq24195ctl"))
+        .arg("--sim-detecting")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}: {:?}", args, output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Standard output of a run that must succeed
+fn succeeds(args: &[&str]) -> String {
+    let output = bq24195ctl(args);
+    assert!(output.status.success(), "{:?}: {:?}", args, output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Standard error of a run that must fail
+fn fails(args: &[&str]) -> String {
+    let output = bq24195ctl(args);
+    assert!(!output.status.success(), "{:?}: {:?}", args, output);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn dump() {
+    let dump = succeeds(&["dump"]);
+    assert_eq!(dump.lines().count(), 12);
+    assert!(dump.contains("0x00 InputSourceControl: EN_HIZ=0 VINDPM=4.36V IINLIM=100mA\n"));
+    assert!(dump.ends_with("unknown, not-charging, no power\n"));
+}
+
+#[test]
+fn get() {
+    assert_eq!(succeeds(&["get", "VREG"]), "VREG=4.208V\n");
+    assert_eq!(succeeds(&["get", "chg_config"]), "CHG_CONFIG=charge\n");
+    assert!(fails(&["get", "VBAT"]).starts_with("unknown field VBAT"));
+}
+
+#[test]
+fn set() {
+    assert_eq!(succeeds(&["set", "IINLIM", "1.5A"]), "IINLIM=1500mA\n");
+    assert_eq!(succeeds(&["set", "WATCHDOG", "off"]), "WATCHDOG=off\n");
+    assert!(fails(&["set", "VREG", "4.2V"]).starts_with("4.2V is not a setting of VREG"));
+    assert_eq!(
+        fails(&["set", "CHRG_STAT", "done"]),
+        "CHRG_STAT is read-only\n"
+    );
+    // A termination current of 2048mA is not below the default 2048mA fast charge current
+    assert!(fails(&["set", "ITERM", "2048mA"]).starts_with("not written"));
+}
+
+#[test]
+fn apply() {
+    let path = env::temp_dir().join(format!("bq24195ctl-apply-{}.conf", std::process::id()));
+    fs::write(&path, "# 4.2V cell\nVREG = 4.192V\n\nICHG=1024mA # 0.5C\n").unwrap();
+    let output = succeeds(&["apply", path.to_str().unwrap()]);
+    fs::write(&path, "VREG 4.192V\n").unwrap();
+    let error = fails(&["apply", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output, "ICHG: 2048mA -> 1024mA\nVREG: 4.208V -> 4.192V\n");
+    assert!(error.ends_with(":1: expected `FIELD = value`\n"));
+}
+
+#[test]
+fn writes_do_not_force_detection() {
+    let path = env::temp_dir().join(format!("bq24195ctl-dpdm-{}.conf", std::process::id()));
+    fs::write(&path, "IINLIM = 1.5A\n").unwrap();
+    let cleared = bq24195ctl_detecting(&["apply", path.to_str().unwrap()]);
+    fs::write(&path, "IINLIM = 1.5A\nDPDM_EN = 1\n").unwrap();
+    let forced = bq24195ctl_detecting(&["apply", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(cleared, "IINLIM: 100mA -> 1500mA\nDPDM_EN: 1 -> 0\n");
+    assert_eq!(forced, "IINLIM: 100mA -> 1500mA\n");
+}
+
+#[test]
+fn watch() {
+    assert_eq!(
+        succeeds(&["watch", "--interval", "0", "--count", "2"]),
+        "unknown, not-charging, no power\n"
+    );
+    assert!(fails(&["watch", "--count"]).starts_with("usage"));
+}
+
+#[test]
+fn calc() {
+    let output = succeeds(&["calc", "VREG=4.2V", "IINLIM=1.4A"]);
+    assert!(
+        output.contains("0x04 = 0xae ChargeVoltageControl: VREG=4.192V BATLOWV=3V VRECHG=100mV\n")
+    );
+    assert!(output.contains("VREG: 4.192V (requested 4.2V)\n"));
+    assert!(output.contains("IINLIM: 1200mA (requested 1400mA)\n"));
+    assert!(succeeds(&["calc", "--c", "BQ", "32", "ICHG=1A"]).starts_with("#define BQ_REG00 0x32 "));
+    assert_eq!(
+        fails(&["calc", "VREG=4.2V", "ICHG"]),
+        "ICHG: expected FIELD=value\n"
+    );
+    assert_eq!(
+        fails(&["calc", "VREG=4.2V", ""]),
+        ": expected FIELD=value\n"
+    );
+    assert_eq!(
+        fails(&["calc", "VREG=4.2V", "é"]),
+        "é: expected FIELD=value\n"
+    );
+}